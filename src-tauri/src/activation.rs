@@ -0,0 +1,362 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::links::{
+    clear_link_path, create_symlink, link_folder_name, link_path_for, link_points_to,
+    managed_copy_owner, remove_symlink, LinkError, MANAGED_MARKER,
+};
+use crate::storage::{load_all_mods, load_settings, save_settings};
+use crate::variants::deployed_path;
+use crate::Mod;
+
+// Prefix XXMI gives folders it should skip
+const DISABLED_PREFIX: &str = "DISABLED_";
+
+// How an active mod is made visible to ZZMI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivationMode {
+    // Symbolic link to the library folder (Developer Mode or admin on Windows)
+    #[default]
+    Symlink,
+    // Directory junction; needs no extra rights. Other platforms use a symlink
+    Junction,
+    // Full copy of the mod folder
+    Copy,
+    // Folder tree of hard links; cheap, but must be on the library's volume
+    HardlinkTree,
+    // XXMI style: the copy stays in the ZZMI folder and is renamed with a
+    // DISABLED_ prefix while the mod is inactive
+    DisabledPrefix,
+}
+
+impl ActivationMode {
+    // Whether ZZMI sees a snapshot of the library folder rather than the
+    // folder itself
+    pub fn copies_files(self) -> bool {
+        matches!(
+            self,
+            ActivationMode::Copy | ActivationMode::HardlinkTree | ActivationMode::DisabledPrefix
+        )
+    }
+}
+
+// One way of putting a mod into the ZZMI folder and taking it out again.
+// Every implementation only ever touches entries that Aether owns.
+pub trait Activator: Send + Sync {
+    fn activate(&self, zzmi_path: &str, m: &Mod) -> Result<(), LinkError>;
+
+    // Returns false when there was nothing of ours to take out
+    fn deactivate(&self, zzmi_path: &str, m: &Mod) -> io::Result<bool>;
+
+    // Whether the mod is really in place, regardless of what we recorded
+    fn is_active(&self, zzmi_path: &str, m: &Mod) -> bool;
+
+    // Remove every trace of the mod from the ZZMI folder before it is deleted
+    fn purge(&self, zzmi_path: &str, m: &Mod) -> io::Result<()> {
+        self.deactivate(zzmi_path, m).map(|_| ())
+    }
+}
+
+pub fn activator_for(mode: ActivationMode) -> Box<dyn Activator> {
+    match mode {
+        ActivationMode::Symlink => Box::new(LinkActivator { junction: false }),
+        ActivationMode::Junction => Box::new(LinkActivator { junction: true }),
+        ActivationMode::Copy => Box::new(CopyActivator { hardlink: false }),
+        ActivationMode::HardlinkTree => Box::new(CopyActivator { hardlink: true }),
+        ActivationMode::DisabledPrefix => Box::new(DisabledPrefixActivator),
+    }
+}
+
+struct LinkActivator {
+    junction: bool,
+}
+
+impl Activator for LinkActivator {
+    fn activate(&self, zzmi_path: &str, m: &Mod) -> Result<(), LinkError> {
+        let src = deployed_path(m);
+        let dst = link_path_for(zzmi_path, m);
+        if self.junction {
+            create_junction(&src, Path::new(&dst))
+        } else {
+            create_symlink(&src, Path::new(&dst))
+        }
+    }
+
+    fn deactivate(&self, zzmi_path: &str, m: &Mod) -> io::Result<bool> {
+        remove_symlink(Path::new(&link_path_for(zzmi_path, m)))
+    }
+
+    fn is_active(&self, zzmi_path: &str, m: &Mod) -> bool {
+        let link_path = link_path_for(zzmi_path, m);
+        let link_path = Path::new(&link_path);
+        link_path.exists() && link_points_to(link_path, m)
+    }
+}
+
+// Junctions only exist on Windows and only work for folders; everywhere else
+// this is a plain symlink
+fn create_junction(src: &Path, dst: &Path) -> Result<(), LinkError> {
+    #[cfg(windows)]
+    {
+        if src.is_dir() {
+            clear_link_path(dst)?;
+            junction::create(src, dst)?;
+            return Ok(());
+        }
+    }
+
+    create_symlink(src, dst)
+}
+
+struct CopyActivator {
+    hardlink: bool,
+}
+
+impl Activator for CopyActivator {
+    fn activate(&self, zzmi_path: &str, m: &Mod) -> Result<(), LinkError> {
+        deploy_copy(m, Path::new(&link_path_for(zzmi_path, m)), self.hardlink)
+    }
+
+    fn deactivate(&self, zzmi_path: &str, m: &Mod) -> io::Result<bool> {
+        remove_managed_copy(Path::new(&link_path_for(zzmi_path, m)), m)
+    }
+
+    fn is_active(&self, zzmi_path: &str, m: &Mod) -> bool {
+        is_copy_of(Path::new(&link_path_for(zzmi_path, m)), m)
+    }
+}
+
+fn is_copy_of(path: &Path, m: &Mod) -> bool {
+    managed_copy_owner(path).as_deref() == Some(m.id.as_str())
+}
+
+// Copy (or hard link) the mod into `dst`. The marker goes in first so that a
+// half-finished copy is still recognised as ours and can be cleaned up.
+fn deploy_copy(m: &Mod, dst: &Path, hardlink: bool) -> Result<(), LinkError> {
+    let src = deployed_path(m);
+    if !src.exists() {
+        return Err(LinkError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Mod folder {} does not exist", src.display()),
+        )));
+    }
+    clear_link_path(dst)?;
+
+    fs::create_dir_all(dst)?;
+    fs::write(dst.join(MANAGED_MARKER), &m.id)?;
+
+    let result = if src.is_dir() {
+        copy_tree(&src, dst, hardlink)
+    } else {
+        // Single-file mods get a folder of their own so the marker has a home
+        let target = dst.join(src.file_name().unwrap_or_default());
+        copy_file(&src, &target, hardlink)
+    };
+
+    if let Err(e) = result {
+        let _ = fs::remove_dir_all(dst);
+        return Err(LinkError::Io(e));
+    }
+    Ok(())
+}
+
+fn copy_file(src: &Path, dst: &Path, hardlink: bool) -> io::Result<()> {
+    if hardlink {
+        fs::hard_link(src, dst).map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!(
+                    "Failed to hard link {} (the library and ZZMI folder must be on the same drive): {}",
+                    src.display(),
+                    e
+                ),
+            )
+        })
+    } else {
+        fs::copy(src, dst).map(|_| ())
+    }
+}
+
+fn copy_tree(src: &Path, dst: &Path, hardlink: bool) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_tree(&entry.path(), &target, hardlink)?;
+        } else {
+            copy_file(&entry.path(), &target, hardlink)?;
+        }
+    }
+    Ok(())
+}
+
+// Delete a deployed copy, but only if it belongs to this mod
+fn remove_managed_copy(path: &Path, m: &Mod) -> io::Result<bool> {
+    if !is_copy_of(path, m) {
+        if fs::symlink_metadata(path).is_ok() {
+            println!(
+                "Leaving {} in place: it is not managed by Aether Manager",
+                path.display()
+            );
+        }
+        return Ok(false);
+    }
+    fs::remove_dir_all(path)?;
+    Ok(true)
+}
+
+struct DisabledPrefixActivator;
+
+fn disabled_path_for(zzmi_path: &str, m: &Mod) -> String {
+    format!("{}/{}{}", zzmi_path, DISABLED_PREFIX, link_folder_name(m))
+}
+
+impl Activator for DisabledPrefixActivator {
+    fn activate(&self, zzmi_path: &str, m: &Mod) -> Result<(), LinkError> {
+        let enabled = link_path_for(zzmi_path, m);
+        let disabled = disabled_path_for(zzmi_path, m);
+
+        if is_copy_of(Path::new(&disabled), m) {
+            clear_link_path(Path::new(&enabled))?;
+            fs::rename(&disabled, &enabled)?;
+            return Ok(());
+        }
+        deploy_copy(m, Path::new(&enabled), false)
+    }
+
+    fn deactivate(&self, zzmi_path: &str, m: &Mod) -> io::Result<bool> {
+        let enabled = link_path_for(zzmi_path, m);
+        let disabled = disabled_path_for(zzmi_path, m);
+
+        if !is_copy_of(Path::new(&enabled), m) {
+            return remove_managed_copy(Path::new(&enabled), m);
+        }
+        remove_managed_copy(Path::new(&disabled), m)?;
+        if fs::symlink_metadata(&disabled).is_ok() {
+            // The user's own folder holds the disabled name; the copy can
+            // always be made again, theirs can't
+            fs::remove_dir_all(&enabled)?;
+        } else {
+            fs::rename(&enabled, &disabled)?;
+        }
+        Ok(true)
+    }
+
+    fn is_active(&self, zzmi_path: &str, m: &Mod) -> bool {
+        is_copy_of(Path::new(&link_path_for(zzmi_path, m)), m)
+    }
+
+    fn purge(&self, zzmi_path: &str, m: &Mod) -> io::Result<()> {
+        remove_managed_copy(Path::new(&link_path_for(zzmi_path, m)), m)?;
+        remove_managed_copy(Path::new(&disabled_path_for(zzmi_path, m)), m)?;
+        Ok(())
+    }
+}
+
+// Bring the deployed copy of a mod up to date after its library folder was
+// edited. Links need nothing; a disabled copy is dropped so the next
+// activation copies the folder afresh.
+pub async fn refresh_deployment(m: &Mod) -> Result<(), String> {
+    let settings = load_settings().await?;
+    let Some(zzmi_path) = settings.zzmi_mods_path else {
+        return Ok(());
+    };
+    if !settings.activation_mode.copies_files() {
+        return Ok(());
+    }
+
+    let activator = activator_for(settings.activation_mode);
+    if m.is_active {
+        activator
+            .activate(&zzmi_path, m)
+            .map_err(|e| format!("Failed to update the deployed copy of {}: {}", m.title, e))
+    } else {
+        activator
+            .purge(&zzmi_path, m)
+            .map_err(|e| format!("Failed to remove the stale copy of {}: {}", m.title, e))
+    }
+}
+
+// ===== Switching modes =====
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModeMigrationReport {
+    pub from: ActivationMode,
+    pub to: ActivationMode,
+    // Active mods that were redeployed with the new mode
+    pub migrated: Vec<String>,
+}
+
+fn switch_mod(
+    from: &dyn Activator,
+    to: &dyn Activator,
+    zzmi_path: &str,
+    m: &Mod,
+) -> Result<(), String> {
+    // Purge rather than deactivate, so no disabled copy is left behind
+    from.purge(zzmi_path, m).map_err(|e| e.to_string())?;
+    if let Err(e) = to.activate(zzmi_path, m) {
+        if let Err(restore) = from.activate(zzmi_path, m) {
+            println!("Failed to restore {}: {}", m.title, restore);
+        }
+        return Err(e.to_string());
+    }
+    Ok(())
+}
+
+// Redeploy every active mod with `mode` and make it the configured mode. If
+// any mod can't be switched, the ones already done are switched back and the
+// setting is left unchanged.
+pub async fn set_activation_mode(mode: ActivationMode) -> Result<ModeMigrationReport, String> {
+    let mut settings = load_settings().await?;
+    let mut report = ModeMigrationReport {
+        from: settings.activation_mode,
+        to: mode,
+        migrated: Vec::new(),
+    };
+    if report.from == mode {
+        return Ok(report);
+    }
+
+    if let Some(zzmi_path) = settings.zzmi_mods_path.as_deref() {
+        let mods = load_all_mods().await?;
+        let from = activator_for(report.from);
+        let to = activator_for(mode);
+
+        // Mods whose folder is gone are left for reconcile to sort out
+        let active: Vec<&Mod> = mods
+            .iter()
+            .filter(|m| m.is_active && Path::new(&m.file_path).exists())
+            .collect();
+
+        for (done, m) in active.iter().enumerate() {
+            if let Err(e) = switch_mod(from.as_ref(), to.as_ref(), zzmi_path, m) {
+                for switched in active[..done].iter().rev() {
+                    if let Err(e) = switch_mod(to.as_ref(), from.as_ref(), zzmi_path, switched) {
+                        println!("Failed to switch {} back: {}", switched.title, e);
+                    }
+                }
+                return Err(format!(
+                    "Failed to switch {} to the new activation mode: {}",
+                    m.title, e
+                ));
+            }
+            report.migrated.push(m.id.clone());
+        }
+
+        // Disabled copies left behind by the old mode are no longer needed
+        for m in mods.iter().filter(|m| !m.is_active) {
+            if let Err(e) = from.purge(zzmi_path, m) {
+                println!("Failed to clean up {}: {}", m.title, e);
+            }
+        }
+    }
+
+    settings.activation_mode = mode;
+    save_settings(&settings).await?;
+    Ok(report)
+}
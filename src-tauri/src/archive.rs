@@ -0,0 +1,278 @@
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+// Archive formats we know how to unpack into the library
+const SUPPORTED_EXTENSIONS: [&str; 3] = ["zip", "7z", "rar"];
+
+// Entries that archivers add on their own and that never belong to a mod
+fn is_junk_entry(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    lower.starts_with('.')
+        || lower == "__macosx"
+        || lower == "thumbs.db"
+        || lower == "desktop.ini"
+}
+
+pub fn is_supported_archive(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| SUPPORTED_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            .unwrap_or(false)
+}
+
+// Name a mod gets when it comes straight from the archive root
+pub fn archive_stem(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+// Resolve an archive entry name below `dest`, rejecting anything that would
+// escape it (absolute paths, drive prefixes, `..` components).
+pub fn safe_join(dest: &Path, entry_name: &str) -> Option<PathBuf> {
+    let normalized = entry_name.replace('\\', "/");
+    let mut out = dest.to_path_buf();
+    let mut has_component = false;
+
+    for component in Path::new(&normalized).components() {
+        match component {
+            Component::Normal(part) => {
+                out.push(part);
+                has_component = true;
+            }
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+
+    // Windows drive letters survive as a normal component on unix ("C:")
+    if normalized.len() >= 2 && normalized.as_bytes()[1] == b':' {
+        return None;
+    }
+
+    if has_component {
+        Some(out)
+    } else {
+        None
+    }
+}
+
+fn unsafe_entry_error(name: &str) -> String {
+    format!("Archive contains an unsafe path: {}", name)
+}
+
+fn write_entry(target: &Path, reader: &mut dyn io::Read) -> io::Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut out = fs::File::create(target)?;
+    io::copy(reader, &mut out)?;
+    Ok(())
+}
+
+fn extract_zip(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    let file =
+        fs::File::open(archive_path).map_err(|e| format!("Failed to open archive: {}", e))?;
+    let mut archive =
+        zip::ZipArchive::new(file).map_err(|e| format!("Failed to read zip archive: {}", e))?;
+
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| format!("Failed to read zip entry: {}", e))?;
+        let name = entry.name().to_string();
+        let target = safe_join(dest, &name).ok_or_else(|| unsafe_entry_error(&name))?;
+
+        if entry.is_dir() {
+            fs::create_dir_all(&target)
+                .map_err(|e| format!("Failed to create directory {}: {}", name, e))?;
+        } else {
+            write_entry(&target, &mut entry)
+                .map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn extract_7z(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    let mut reader = sevenz_rust::SevenZReader::open(archive_path, sevenz_rust::Password::empty())
+        .map_err(|e| format!("Failed to read 7z archive: {}", e))?;
+
+    reader
+        .for_each_entries(|entry, data| {
+            let name = entry.name().to_string();
+            let target = safe_join(dest, &name)
+                .ok_or_else(|| sevenz_rust::Error::other(unsafe_entry_error(&name)))?;
+
+            if entry.is_directory() {
+                fs::create_dir_all(&target).map_err(sevenz_rust::Error::io)?;
+            } else if entry.has_stream() {
+                write_entry(&target, data).map_err(sevenz_rust::Error::io)?;
+            } else {
+                // Empty files carry no stream at all
+                write_entry(&target, &mut io::empty()).map_err(sevenz_rust::Error::io)?;
+            }
+            Ok(true)
+        })
+        .map_err(|e| format!("Failed to extract 7z archive: {}", e))
+}
+
+fn extract_rar(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    let mut archive = unrar::Archive::new(archive_path)
+        .open_for_processing()
+        .map_err(|e| format!("Failed to read rar archive: {}", e))?;
+
+    while let Some(header) = archive
+        .read_header()
+        .map_err(|e| format!("Failed to read rar entry: {}", e))?
+    {
+        let name = header.entry().filename.to_string_lossy().to_string();
+        let target = safe_join(dest, &name).ok_or_else(|| unsafe_entry_error(&name))?;
+
+        archive = if header.entry().is_file() {
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .map_err(|e| format!("Failed to create directory for {}: {}", name, e))?;
+            }
+            header
+                .extract_to(&target)
+                .map_err(|e| format!("Failed to extract {}: {}", name, e))?
+        } else {
+            fs::create_dir_all(&target)
+                .map_err(|e| format!("Failed to create directory {}: {}", name, e))?;
+            header
+                .skip()
+                .map_err(|e| format!("Failed to read rar entry: {}", e))?
+        };
+    }
+
+    Ok(())
+}
+
+// Unpack a supported archive into `dest`
+pub fn extract_archive(archive_path: &Path, dest: &Path) -> Result<(), String> {
+    fs::create_dir_all(dest)
+        .map_err(|e| format!("Failed to create extraction directory: {}", e))?;
+
+    let extension = archive_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "zip" => extract_zip(archive_path, dest),
+        "7z" => extract_7z(archive_path, dest),
+        "rar" => extract_rar(archive_path, dest),
+        other => Err(format!("Unsupported archive format: {}", other)),
+    }
+}
+
+// Walk down through wrapper folders (a single sub-folder and nothing else)
+// until we reach the folder that actually holds the mod files.
+pub fn find_mod_root(extracted: &Path) -> Result<PathBuf, String> {
+    let mut current = extracted.to_path_buf();
+
+    loop {
+        let mut dirs = Vec::new();
+        let mut has_files = false;
+
+        for entry in fs::read_dir(&current)
+            .map_err(|e| format!("Failed to read extracted archive: {}", e))?
+        {
+            let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+            if is_junk_entry(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            if entry.path().is_dir() {
+                dirs.push(entry.path());
+            } else {
+                has_files = true;
+            }
+        }
+
+        if dirs.len() == 1 && !has_files {
+            current = dirs.remove(0);
+        } else {
+            return Ok(current);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("aether-archive-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn safe_join_keeps_entries_inside_dest() {
+        let dest = Path::new("/tmp/dest");
+        assert_eq!(
+            safe_join(dest, "Mod/sub\\file.ini"),
+            Some(dest.join("Mod/sub/file.ini"))
+        );
+        assert_eq!(safe_join(dest, "./Mod/a.dds"), Some(dest.join("Mod/a.dds")));
+    }
+
+    #[test]
+    fn safe_join_rejects_escaping_entries() {
+        let dest = Path::new("/tmp/dest");
+        for name in [
+            "../evil.ini",
+            "Mod/../../evil.ini",
+            "..\\evil.ini",
+            "/etc/passwd",
+            "\\Windows\\evil.dll",
+            "C:\\Windows\\evil.dll",
+            "C:evil.dll",
+            "",
+            ".",
+        ] {
+            assert_eq!(safe_join(dest, name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn zip_slip_entries_fail_extraction() {
+        let dir = temp_dir("zipslip");
+        let archive_path = dir.join("evil.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&archive_path).unwrap());
+        writer
+            .start_file("../escaped.ini", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"[Constants]\n").unwrap();
+        writer.finish().unwrap();
+
+        let dest = dir.join("out");
+        let err = extract_archive(&archive_path, &dest).unwrap_err();
+        assert!(err.contains("unsafe path"), "{}", err);
+        assert!(!dir.join("escaped.ini").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn find_mod_root_skips_wrapper_folders_and_junk() {
+        let dir = temp_dir("root");
+        let root = dir.join("Wrapper/Mod");
+        fs::create_dir_all(root.join("Textures")).unwrap();
+        fs::write(root.join("mod.ini"), "").unwrap();
+        fs::create_dir_all(dir.join("__MACOSX")).unwrap();
+        fs::write(dir.join("Wrapper/.DS_Store"), "").unwrap();
+
+        assert_eq!(find_mod_root(&dir).unwrap(), root);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
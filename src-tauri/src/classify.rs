@@ -0,0 +1,417 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+use crate::get_app_config_dir;
+use crate::ini::{parse_ini, read_ini};
+use crate::manifest::{ini_files_at, manifest_for, override_kind};
+use crate::storage::load_all_mods;
+
+// Roster ids are the ones the UI uses for `Mod.character`. Draw hashes change
+// with game updates and none have been confirmed for the bundled list yet, so
+// out of the box only names and aliases match, and those are only suggested.
+// Hashes go in `character_hashes.json` in the app config folder, which is
+// merged on top; entries there that carry hashes make the hash match take over.
+const BUNDLED_DATABASE: &str = include_str!("../resources/character_hashes.json");
+const USER_DATABASE_FILE: &str = "character_hashes.json";
+
+// Tokens this short only match exactly; longer ones tolerate one typo
+const FUZZY_MIN_LEN: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetKind {
+    #[default]
+    Character,
+    Bangboo,
+    WEngine,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseEntry {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub kind: TargetKind,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub hashes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CharacterDatabase {
+    #[serde(default)]
+    pub version: u32,
+    #[serde(default)]
+    pub entries: Vec<DatabaseEntry>,
+}
+
+impl CharacterDatabase {
+    // Entries with a known id gain the other file's aliases and hashes; new
+    // ids are added as they are
+    fn merge(&mut self, other: CharacterDatabase) {
+        for entry in other.entries {
+            match self
+                .entries
+                .iter_mut()
+                .find(|e| e.id.eq_ignore_ascii_case(&entry.id))
+            {
+                Some(existing) => {
+                    existing.aliases.extend(entry.aliases);
+                    existing.hashes.extend(entry.hashes);
+                }
+                None => self.entries.push(entry),
+            }
+        }
+    }
+}
+
+// The bundled database with the user's additions on top
+pub fn load_database() -> Result<CharacterDatabase, String> {
+    let mut database: CharacterDatabase = serde_json::from_str(BUNDLED_DATABASE)
+        .map_err(|e| format!("Bundled character database is invalid: {}", e))?;
+
+    let user_file = get_app_config_dir()?.join(USER_DATABASE_FILE);
+    if user_file.exists() {
+        let text = fs::read_to_string(&user_file)
+            .map_err(|e| format!("Failed to read {}: {}", user_file.display(), e))?;
+        let user: CharacterDatabase = serde_json::from_str(&text)
+            .map_err(|e| format!("Failed to parse {}: {}", user_file.display(), e))?;
+        database.merge(user);
+    }
+    Ok(database)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchSource {
+    Hash,
+    Name,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterCandidate {
+    pub id: String,
+    pub name: String,
+    pub kind: TargetKind,
+    pub source: MatchSource,
+    // Matching hashes, or matching name words
+    pub score: usize,
+    pub matched_hashes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacterSuggestion {
+    // Best first
+    pub candidates: Vec<CharacterCandidate>,
+    // Set when one roster character wins on draw hashes and the mod can be
+    // filed under it without asking. Name matches stay in `candidates`.
+    pub character: Option<String>,
+}
+
+// `EllenJoe_v2-Maid` -> ["ellen", "joe", "v2", "maid"]
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            previous = None;
+            continue;
+        }
+        let boundary =
+            c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit());
+        if boundary && !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+        current.extend(c.to_lowercase());
+        previous = Some(c);
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+// Whether two words are at most one edit apart
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+    let prefix = short
+        .iter()
+        .zip(long.iter())
+        .take_while(|(x, y)| x == y)
+        .count();
+    if prefix == short.len() {
+        return true;
+    }
+    if short.len() == long.len() {
+        short[prefix + 1..] == long[prefix + 1..]
+    } else {
+        short[prefix..] == long[prefix + 1..]
+    }
+}
+
+fn token_matches(token: &str, word: &str, fuzzy: bool) -> bool {
+    token == word
+        || (fuzzy && word.chars().count() >= FUZZY_MIN_LEN && within_one_edit(token, word))
+}
+
+// How many words of `name` appear in order in `tokens`, either one token per
+// word or run together into a single token (`zhuyuan`)
+fn name_score(tokens: &[String], name: &str, fuzzy: bool) -> usize {
+    let words = tokenize(name);
+    if words.is_empty() {
+        return 0;
+    }
+    let joined = words.concat();
+    let spread = tokens.windows(words.len()).any(|window| {
+        window
+            .iter()
+            .zip(&words)
+            .all(|(token, word)| token_matches(token, word, fuzzy))
+    });
+    if spread || tokens.iter().any(|t| token_matches(t, &joined, fuzzy)) {
+        words.len()
+    } else {
+        0
+    }
+}
+
+fn hash_candidates(
+    database: &CharacterDatabase,
+    hashes: &BTreeSet<String>,
+) -> Vec<CharacterCandidate> {
+    database
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let mut matched: Vec<String> = entry
+                .hashes
+                .iter()
+                .map(|h| h.trim().to_lowercase())
+                .filter(|h| hashes.contains(h))
+                .collect();
+            matched.sort();
+            matched.dedup();
+            (!matched.is_empty()).then(|| CharacterCandidate {
+                id: entry.id.clone(),
+                name: entry.name.clone(),
+                kind: entry.kind,
+                source: MatchSource::Hash,
+                score: matched.len(),
+                matched_hashes: matched,
+            })
+        })
+        .collect()
+}
+
+fn name_candidates(
+    database: &CharacterDatabase,
+    names: &[&str],
+    fuzzy: bool,
+) -> Vec<CharacterCandidate> {
+    let tokens: Vec<Vec<String>> = names.iter().map(|n| tokenize(n)).collect();
+    database
+        .entries
+        .iter()
+        .filter_map(|entry| {
+            let score = std::iter::once(&entry.id)
+                .chain(std::iter::once(&entry.name))
+                .chain(entry.aliases.iter())
+                .flat_map(|name| tokens.iter().map(move |t| name_score(t, name, fuzzy)))
+                .max()
+                .unwrap_or(0);
+            (score > 0).then(|| CharacterCandidate {
+                id: entry.id.clone(),
+                name: entry.name.clone(),
+                kind: entry.kind,
+                source: MatchSource::Name,
+                score,
+                matched_hashes: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+// The top candidate, if nothing else has the same score
+fn sole_winner(candidates: &[CharacterCandidate]) -> Option<&CharacterCandidate> {
+    match candidates {
+        [first, second, ..] if second.score == first.score => None,
+        [first, ..] => Some(first),
+        [] => None,
+    }
+}
+
+// Rank roster entries for a mod by the draw hashes it overrides, falling back
+// to its title and folder name when no hash is known
+pub fn classify(
+    database: &CharacterDatabase,
+    hashes: &BTreeSet<String>,
+    names: &[&str],
+) -> CharacterSuggestion {
+    let mut candidates = hash_candidates(database, hashes);
+    if candidates.is_empty() {
+        candidates = name_candidates(database, names, false);
+    }
+    if candidates.is_empty() {
+        candidates = name_candidates(database, names, true);
+    }
+    candidates.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.id.cmp(&b.id)));
+
+    // A title can name a character the mod doesn't touch, so a name match is
+    // only ever a suggestion
+    let character = sole_winner(&candidates)
+        .filter(|c| c.kind == TargetKind::Character && c.source == MatchSource::Hash)
+        .map(|c| c.id.clone());
+    CharacterSuggestion {
+        candidates,
+        character,
+    }
+}
+
+// Override hashes of a mod that isn't in the library yet
+fn folder_hashes(root: &Path) -> Result<BTreeSet<String>, String> {
+    let (_, files) = ini_files_at(root)?;
+    let mut hashes = BTreeSet::new();
+    for file in files {
+        let text =
+            read_ini(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        for section in parse_ini(&text).sections {
+            if let (Some(_), Some(hash)) = (override_kind(&section), section.get("hash")) {
+                hashes.insert(hash.to_lowercase());
+            }
+        }
+    }
+    Ok(hashes)
+}
+
+// The character a mod being installed from `root` should be filed under, if
+// its hashes say so. Never fails the install.
+pub fn detect_character(root: &Path, names: &[&str]) -> Option<String> {
+    let result = load_database().and_then(|database| {
+        let hashes = folder_hashes(root)?;
+        Ok(classify(&database, &hashes, names))
+    });
+    match result {
+        Ok(suggestion) => suggestion.character,
+        Err(e) => {
+            println!("Warning: could not classify {}: {}", root.display(), e);
+            None
+        }
+    }
+}
+
+pub async fn suggest_character(mod_id: &str) -> Result<CharacterSuggestion, String> {
+    let m = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or("Mod not found")?;
+    let database = load_database()?;
+    let manifest = manifest_for(&m).await?;
+    let hashes = manifest.overrides.into_iter().map(|o| o.hash).collect();
+    Ok(classify(&database, &hashes, &[&m.title, &m.original_name]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, name: &str, aliases: &[&str], hashes: &[&str]) -> DatabaseEntry {
+        DatabaseEntry {
+            id: id.to_string(),
+            name: name.to_string(),
+            kind: TargetKind::Character,
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            hashes: hashes.iter().map(|h| h.to_string()).collect(),
+        }
+    }
+
+    fn database() -> CharacterDatabase {
+        CharacterDatabase {
+            version: 1,
+            entries: vec![
+                entry("ellen", "Ellen", &["ellen joe"], &["1A2B3C4D"]),
+                entry("zhuyuan", "Zhu Yuan", &[], &[]),
+                entry("nicole", "Nicole", &["nicole demara"], &[]),
+            ],
+        }
+    }
+
+    #[test]
+    fn tokenize_splits_case_digits_and_separators() {
+        assert_eq!(tokenize("EllenJoe_v2-Maid"), ["ellen", "joe", "v2", "maid"]);
+        assert_eq!(tokenize("ZHU YUAN (Police)"), ["zhu", "yuan", "police"]);
+        assert_eq!(tokenize("Mod2Skin"), ["mod2", "skin"]);
+        assert!(tokenize("__--").is_empty());
+    }
+
+    #[test]
+    fn within_one_edit_allows_one_change() {
+        assert!(within_one_edit("nicole", "nicole"));
+        assert!(within_one_edit("nicole", "nichole"));
+        assert!(within_one_edit("nichole", "nicole"));
+        assert!(within_one_edit("nicole", "nicale"));
+        assert!(within_one_edit("nicole", "nicol"));
+        assert!(!within_one_edit("nicole", "nciole"));
+        assert!(!within_one_edit("nicole", "nico"));
+        assert!(within_one_edit("", "a"));
+    }
+
+    #[test]
+    fn hashes_win_over_names() {
+        let hashes = BTreeSet::from(["1a2b3c4d".to_string()]);
+        let suggestion = classify(&database(), &hashes, &["Nicole outfit"]);
+        assert_eq!(suggestion.character.as_deref(), Some("ellen"));
+        assert_eq!(suggestion.candidates[0].source, MatchSource::Hash);
+        assert_eq!(suggestion.candidates[0].matched_hashes, ["1a2b3c4d"]);
+    }
+
+    #[test]
+    fn names_match_spread_or_joined() {
+        let none = BTreeSet::new();
+        let spread = classify(&database(), &none, &["Zhu_Yuan_Swimsuit"]);
+        assert_eq!(spread.candidates[0].id, "zhuyuan");
+        let joined = classify(&database(), &none, &["ZhuyuanSwimsuit"]);
+        assert_eq!(joined.candidates[0].id, "zhuyuan");
+    }
+
+    #[test]
+    fn names_are_only_suggested() {
+        let suggestion = classify(&database(), &BTreeSet::new(), &["Zhu Yuan police"]);
+        assert_eq!(suggestion.candidates.len(), 1);
+        assert_eq!(suggestion.candidates[0].source, MatchSource::Name);
+        assert_eq!(suggestion.character, None);
+    }
+
+    #[test]
+    fn typos_are_only_suggested() {
+        let suggestion = classify(&database(), &BTreeSet::new(), &["Nichole bunny"]);
+        assert_eq!(suggestion.candidates[0].id, "nicole");
+        assert_eq!(suggestion.character, None);
+    }
+
+    #[test]
+    fn ties_are_not_decided() {
+        let suggestion = classify(&database(), &BTreeSet::new(), &["Ellen and Nicole"]);
+        assert_eq!(suggestion.candidates.len(), 2);
+        assert_eq!(suggestion.character, None);
+    }
+}
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::manifest::{manifest_for, ModManifest, OverrideKind};
+use crate::presets::find_preset;
+use crate::storage::load_all_mods;
+use crate::Mod;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictingMod {
+    pub mod_id: String,
+    pub title: String,
+    pub ini_file: String,
+    pub section: String,
+}
+
+// Two or more mods overriding the same draw call
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashConflict {
+    pub kind: OverrideKind,
+    pub hash: String,
+    pub match_first_index: Option<String>,
+    pub mods: Vec<ConflictingMod>,
+}
+
+impl HashConflict {
+    pub fn involves(&self, mod_id: &str) -> bool {
+        self.mods.iter().any(|m| m.mod_id == mod_id)
+    }
+}
+
+// Manifests for `mods`; a mod whose folder can't be read is left out, it
+// cannot be loaded by the game either
+pub async fn load_manifests(mods: &[&Mod]) -> Vec<ModManifest> {
+    let mut manifests = Vec::new();
+    for m in mods {
+        match manifest_for(m).await {
+            Ok(manifest) => manifests.push(manifest),
+            Err(e) => println!("Skipping {} in conflict check: {}", m.title, e),
+        }
+    }
+    manifests
+}
+
+// Overrides are grouped by kind, hash and match_first_index: the same hash
+// with a different index is a different draw call and does not collide
+pub fn find_hash_conflicts(mods: &[&Mod], manifests: &[ModManifest]) -> Vec<HashConflict> {
+    let mut groups: BTreeMap<(OverrideKind, String, Option<String>), Vec<ConflictingMod>> =
+        BTreeMap::new();
+
+    for manifest in manifests {
+        let Some(m) = mods.iter().find(|m| m.id == manifest.mod_id) else {
+            continue;
+        };
+        for o in &manifest.overrides {
+            groups
+                .entry((o.kind, o.hash.clone(), o.match_first_index.clone()))
+                .or_default()
+                .push(ConflictingMod {
+                    mod_id: m.id.clone(),
+                    title: m.title.clone(),
+                    ini_file: o.ini_file.clone(),
+                    section: o.section.clone(),
+                });
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, owners)| {
+            let distinct: HashSet<&str> = owners.iter().map(|o| o.mod_id.as_str()).collect();
+            distinct.len() > 1
+        })
+        .map(|((kind, hash, match_first_index), mods)| HashConflict {
+            kind,
+            hash,
+            match_first_index,
+            mods,
+        })
+        .collect()
+}
+
+pub async fn conflicts_among(mods: &[&Mod]) -> Vec<HashConflict> {
+    let manifests = load_manifests(mods).await;
+    find_hash_conflicts(mods, &manifests)
+}
+
+// Conflicts that activating `added` on top of `active` would bring in
+pub async fn conflicts_introduced(active: &[&Mod], added: &[&Mod]) -> Vec<HashConflict> {
+    let added_ids: HashSet<&str> = added.iter().map(|m| m.id.as_str()).collect();
+    let mut combined: Vec<&Mod> = active
+        .iter()
+        .copied()
+        .filter(|m| !added_ids.contains(m.id.as_str()))
+        .collect();
+    combined.extend_from_slice(added);
+
+    conflicts_among(&combined)
+        .await
+        .into_iter()
+        .filter(|c| added_ids.iter().any(|id| c.involves(id)))
+        .collect()
+}
+
+// The mods of a preset, or the active ones when no preset is given
+pub async fn selected_mods(preset_id: Option<&str>) -> Result<Vec<Mod>, String> {
+    let mods = load_all_mods().await?;
+    Ok(match preset_id {
+        Some(preset_id) => {
+            let preset = find_preset(preset_id).await?;
+            mods.into_iter()
+                .filter(|m| preset.mod_ids.contains(&m.id))
+                .collect()
+        }
+        None => mods.into_iter().filter(|m| m.is_active).collect(),
+    })
+}
+
+// Hash collisions between the active mods, or between the mods of a preset
+pub async fn detect_conflicts(preset_id: Option<&str>) -> Result<Vec<HashConflict>, String> {
+    let mods = selected_mods(preset_id).await?;
+    let selected: Vec<&Mod> = mods.iter().collect();
+    Ok(conflicts_among(&selected).await)
+}
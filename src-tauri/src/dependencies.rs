@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::exclusive::{check_exclusive, displaced_by};
+use crate::storage::{load_all_mods, save_mod_metadata};
+use crate::{AppSettings, Mod};
+
+// Some mods only work with a shared helper pack (shader fixes, common texture
+// libraries) or with files that ship with ZZMI itself. Required files are
+// given relative to the ZZMI folder, the one holding d3dx.ini and Mods.
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Dependency {
+    Mod { mod_id: String },
+    File { path: String },
+}
+
+fn zzmi_root(zzmi_path: &str) -> PathBuf {
+    let mods_dir = Path::new(zzmi_path);
+    mods_dir.parent().unwrap_or(mods_dir).to_path_buf()
+}
+
+// Every mod `m` needs, directly or through another dependency, that is not
+// in `seen` yet; dependencies come before the mods that need them
+fn collect<'a>(
+    zzmi_path: &str,
+    mods: &'a [Mod],
+    m: &Mod,
+    seen: &mut HashSet<String>,
+    out: &mut Vec<&'a Mod>,
+) -> Result<(), String> {
+    for dependency in &m.dependencies {
+        match dependency {
+            Dependency::File { path } => {
+                if !zzmi_root(zzmi_path).join(path).exists() {
+                    return Err(format!(
+                        "{} needs {} in the ZZMI folder, which is missing",
+                        m.title, path
+                    ));
+                }
+            }
+            Dependency::Mod { mod_id } => {
+                if !seen.insert(mod_id.clone()) {
+                    continue;
+                }
+                let needed = mods.iter().find(|d| &d.id == mod_id).ok_or_else(|| {
+                    format!(
+                        "{} needs a mod that is no longer in the library ({})",
+                        m.title, mod_id
+                    )
+                })?;
+                if needed.is_missing || !Path::new(&needed.file_path).exists() {
+                    return Err(format!(
+                        "{} needs {}, whose folder is missing",
+                        m.title, needed.title
+                    ));
+                }
+                collect(zzmi_path, mods, needed, seen, out)?;
+                out.push(needed);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Inactive mods to activate along with `m`, dependencies first. Fails with
+// the reason when `m` can't work: something it needs is gone, or would push
+// an active mod out of its exclusive slot.
+pub fn activation_dependencies<'a>(
+    settings: &AppSettings,
+    zzmi_path: &str,
+    mods: &'a [Mod],
+    m: &Mod,
+) -> Result<Vec<&'a Mod>, String> {
+    let mut seen = HashSet::from([m.id.clone()]);
+    let mut needed = Vec::new();
+    collect(zzmi_path, mods, m, &mut seen, &mut needed)?;
+    needed.retain(|d| !d.is_active);
+
+    for dependency in &needed {
+        if let Some(active) = displaced_by(settings, mods, dependency).first() {
+            return Err(format!(
+                "{} needs {}, but {} is already active in its slot",
+                m.title, dependency.title, active.title
+            ));
+        }
+    }
+    Ok(needed)
+}
+
+// `mod_ids` with every dependency they need added, and the ids that were
+// added. Fails when a dependency is missing or two mods would share an
+// exclusive slot.
+pub fn with_dependencies(
+    settings: &AppSettings,
+    zzmi_path: &str,
+    mods: &[Mod],
+    mod_ids: &[String],
+) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut seen: HashSet<String> = mod_ids.iter().cloned().collect();
+    let mut needed = Vec::new();
+    for m in mods.iter().filter(|m| mod_ids.contains(&m.id)) {
+        collect(zzmi_path, mods, m, &mut seen, &mut needed)?;
+    }
+
+    let added: Vec<String> = needed.iter().map(|m| m.id.clone()).collect();
+    let all: Vec<String> = added.iter().chain(mod_ids).cloned().collect();
+    check_exclusive(settings, mods, &all)?;
+    Ok((all, added))
+}
+
+// Mods that list `mod_id` as a dependency
+pub fn dependents_of<'a>(mods: &'a [Mod], mod_id: &str) -> Vec<&'a Mod> {
+    mods.iter()
+        .filter(|m| {
+            m.dependencies
+                .iter()
+                .any(|d| matches!(d, Dependency::Mod { mod_id: id } if id == mod_id))
+        })
+        .collect()
+}
+
+// Stay inside the ZZMI folder
+fn is_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && path.as_bytes().get(1) != Some(&b':')
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir))
+}
+
+pub async fn set_mod_dependencies(
+    mod_id: &str,
+    dependencies: Vec<Dependency>,
+) -> Result<Mod, String> {
+    let mods = load_all_mods().await?;
+    let mut m = mods
+        .iter()
+        .find(|m| m.id == mod_id)
+        .cloned()
+        .ok_or("Mod not found")?;
+
+    let mut checked = Vec::new();
+    for dependency in dependencies {
+        let dependency = match dependency {
+            Dependency::File { path } => Dependency::File {
+                path: path.trim().replace('\\', "/"),
+            },
+            other => other,
+        };
+        match &dependency {
+            Dependency::Mod { mod_id: id } if id == mod_id => {
+                return Err("A mod can't depend on itself".to_string());
+            }
+            Dependency::Mod { mod_id: id } if !mods.iter().any(|d| &d.id == id) => {
+                return Err(format!("Mod {} not found", id));
+            }
+            Dependency::File { path } if !is_relative_path(path) => {
+                return Err(format!(
+                    "{} must be a path inside the ZZMI folder, like ShaderFixes/fix.ini",
+                    path
+                ));
+            }
+            _ => {}
+        }
+        if !checked.contains(&dependency) {
+            checked.push(dependency);
+        }
+    }
+
+    m.dependencies = checked;
+    save_mod_metadata(&m).await?;
+    Ok(m)
+}
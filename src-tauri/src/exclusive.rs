@@ -0,0 +1,161 @@
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::storage::{load_all_mods, load_settings, save_mod_metadata, save_settings};
+use crate::{AppSettings, Mod, Preset};
+
+// A mod's slot is its custom group, or else its character. Slots listed in
+// `AppSettings::exclusive_slots` hold one active mod at a time; every other
+// slot can have any number.
+
+pub fn slot_of(m: &Mod) -> Option<&str> {
+    m.group.as_deref().or(m.character.as_deref())
+}
+
+// The mod's slot, if only one of its mods may be active
+pub fn exclusive_slot<'a>(settings: &AppSettings, m: &'a Mod) -> Option<&'a str> {
+    slot_of(m).filter(|slot| {
+        settings
+            .exclusive_slots
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(slot))
+    })
+}
+
+// Active mods that have to make way when `m` is activated
+pub fn displaced_by<'a>(settings: &AppSettings, mods: &'a [Mod], m: &Mod) -> Vec<&'a Mod> {
+    let Some(slot) = exclusive_slot(settings, m) else {
+        return Vec::new();
+    };
+    mods.iter()
+        .filter(|other| other.is_active && other.id != m.id)
+        .filter(|other| {
+            exclusive_slot(settings, other).is_some_and(|s| s.eq_ignore_ascii_case(slot))
+        })
+        .collect()
+}
+
+// Mods of one exclusive slot that were asked for together
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlotConflict {
+    pub slot: String,
+    // The one that is used; it came first
+    pub kept: String,
+    pub dropped: Vec<String>,
+}
+
+// Keep the first of `mod_ids` in each exclusive slot. Returns the ids that
+// remain, in order, and what was dropped. Unknown ids are kept as they are.
+pub fn enforce_exclusive(
+    settings: &AppSettings,
+    mods: &[Mod],
+    mod_ids: &[String],
+) -> (Vec<String>, Vec<SlotConflict>) {
+    let mut kept = Vec::new();
+    let mut conflicts: Vec<SlotConflict> = Vec::new();
+    let mut by_slot: HashMap<String, usize> = HashMap::new();
+
+    for id in mod_ids {
+        let slot = mods
+            .iter()
+            .find(|m| &m.id == id)
+            .and_then(|m| exclusive_slot(settings, m));
+        let Some(slot) = slot else {
+            kept.push(id.clone());
+            continue;
+        };
+        match by_slot.get(&slot.to_lowercase()) {
+            Some(&index) => conflicts[index].dropped.push(id.clone()),
+            None => {
+                by_slot.insert(slot.to_lowercase(), conflicts.len());
+                conflicts.push(SlotConflict {
+                    slot: slot.to_string(),
+                    kept: id.clone(),
+                    dropped: Vec::new(),
+                });
+                kept.push(id.clone());
+            }
+        }
+    }
+
+    conflicts.retain(|c| !c.dropped.is_empty());
+    (kept, conflicts)
+}
+
+// The preset as it can actually be applied
+pub fn effective_preset(
+    settings: &AppSettings,
+    mods: &[Mod],
+    preset: &Preset,
+) -> (Preset, Vec<SlotConflict>) {
+    let (mod_ids, conflicts) = enforce_exclusive(settings, mods, &preset.mod_ids);
+    let mut effective = preset.clone();
+    effective.mod_ids = mod_ids;
+    (effective, conflicts)
+}
+
+// Refuse a preset that asks for two mods of one exclusive slot
+pub fn check_exclusive(
+    settings: &AppSettings,
+    mods: &[Mod],
+    mod_ids: &[String],
+) -> Result<(), String> {
+    let (_, conflicts) = enforce_exclusive(settings, mods, mod_ids);
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+
+    let title = |id: &String| {
+        mods.iter()
+            .find(|m| &m.id == id)
+            .map(|m| m.title.clone())
+            .unwrap_or_else(|| id.clone())
+    };
+    let details: Vec<String> = conflicts
+        .iter()
+        .map(|c| {
+            let titles: Vec<String> = std::iter::once(&c.kept)
+                .chain(&c.dropped)
+                .map(title)
+                .collect();
+            format!("{} ({})", c.slot, titles.join(", "))
+        })
+        .collect();
+    Err(format!(
+        "Only one mod per exclusive slot can be active: {}",
+        details.join("; ")
+    ))
+}
+
+// Put the mod in a custom slot, or back in its character's with None
+pub async fn set_mod_group(mod_id: &str, group: Option<String>) -> Result<Mod, String> {
+    let mut m = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or("Mod not found")?;
+    m.group = group
+        .map(|g| g.trim().to_string())
+        .filter(|g| !g.is_empty());
+    save_mod_metadata(&m).await?;
+    Ok(m)
+}
+
+// Replace the slots that hold one active mod. Mods already active in them
+// stay so until the next activation there.
+pub async fn set_exclusive_slots(slots: Vec<String>) -> Result<Vec<String>, String> {
+    let mut settings = load_settings().await?;
+    settings.exclusive_slots.clear();
+    for slot in slots.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+        if !settings
+            .exclusive_slots
+            .iter()
+            .any(|s| s.eq_ignore_ascii_case(slot))
+        {
+            settings.exclusive_slots.push(slot.to_string());
+        }
+    }
+    save_settings(&settings).await?;
+    Ok(settings.exclusive_slots)
+}
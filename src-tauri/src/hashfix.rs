@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::activation::refresh_deployment;
+use crate::ini::{parse_ini, read_ini, IniEdit};
+use crate::manifest::{mod_ini_files, relative_path};
+use crate::originals::edit_ini_files;
+use crate::storage::load_all_mods;
+use crate::Mod;
+
+// Game updates change some draw hashes. A mapping file lists the changes per
+// patch, oldest first:
+//
+//   { "patches": [ { "version": "1.6", "hashes": { "<old>": "<new>" } } ] }
+//
+// A hash is carried through every later patch, so a mod made for 1.5 is
+// brought up to date by one run over a file covering 1.6 and 2.0.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashPatch {
+    pub version: String,
+    pub hashes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashMappingTable {
+    pub patches: Vec<HashPatch>,
+}
+
+fn is_hash(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+pub fn load_mapping_table(path: &Path) -> Result<HashMappingTable, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut table: HashMappingTable = serde_json::from_str(&text)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    for patch in &mut table.patches {
+        let mut normalized = BTreeMap::new();
+        for (old, new) in &patch.hashes {
+            let (old, new) = (old.trim().to_lowercase(), new.trim().to_lowercase());
+            if !is_hash(&old) || !is_hash(&new) {
+                return Err(format!(
+                    "Patch {} maps '{}' to '{}'; both must be hex hashes",
+                    patch.version, old, new
+                ));
+            }
+            normalized.insert(old, new);
+        }
+        patch.hashes = normalized;
+    }
+    Ok(table)
+}
+
+impl HashMappingTable {
+    // The current hash for `hash` and the patch that last changed it
+    fn resolve(&self, hash: &str) -> Option<(String, &str)> {
+        let mut current = hash.to_lowercase();
+        let mut changed_in = None;
+        for patch in &self.patches {
+            if let Some(new) = patch.hashes.get(&current) {
+                current = new.clone();
+                changed_in = Some(patch.version.as_str());
+            }
+        }
+        changed_in
+            .filter(|_| current != hash.to_lowercase())
+            .map(|version| (current, version))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashChange {
+    pub ini_file: String,
+    pub section: String,
+    pub line: usize,
+    pub old_hash: String,
+    pub new_hash: String,
+    // Patch the new hash comes from
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModHashFix {
+    pub mod_id: String,
+    pub title: String,
+    pub changes: Vec<HashChange>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashFixFailure {
+    pub mod_id: String,
+    pub title: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashFixReport {
+    pub versions: Vec<String>,
+    // Mods with at least one outdated hash; untouched mods are left out
+    pub mods: Vec<ModHashFix>,
+    pub failed: Vec<HashFixFailure>,
+}
+
+// Edits to make, per ini file
+type FileEdits = Vec<(PathBuf, Vec<IniEdit>)>;
+
+// Every outdated `hash =` line in the mod, grouped by ini file
+fn plan_mod(m: &Mod, table: &HashMappingTable) -> Result<(ModHashFix, FileEdits), String> {
+    let (root, files) = mod_ini_files(m)?;
+    let mut fix = ModHashFix {
+        mod_id: m.id.clone(),
+        title: m.title.clone(),
+        changes: Vec::new(),
+    };
+    let mut edits = Vec::new();
+
+    for file in files {
+        let ini_file = relative_path(&root, &file);
+        let text = read_ini(&file).map_err(|e| format!("Failed to read {}: {}", ini_file, e))?;
+        let mut file_edits = Vec::new();
+        for section in parse_ini(&text).sections {
+            for entry in section
+                .entries
+                .iter()
+                .filter(|e| e.key.eq_ignore_ascii_case("hash"))
+            {
+                let Some((new_hash, version)) = table.resolve(&entry.value) else {
+                    continue;
+                };
+                file_edits.push(IniEdit::SetValue {
+                    line: entry.line,
+                    value: new_hash.clone(),
+                });
+                fix.changes.push(HashChange {
+                    ini_file: ini_file.clone(),
+                    section: section.name.clone(),
+                    line: entry.line,
+                    old_hash: entry.value.clone(),
+                    new_hash,
+                    version: version.to_string(),
+                });
+            }
+        }
+        if !file_edits.is_empty() {
+            edits.push((file, file_edits));
+        }
+    }
+    Ok((fix, edits))
+}
+
+async fn selected_mods(mod_ids: Option<Vec<String>>) -> Result<Vec<Mod>, String> {
+    let mods = load_all_mods().await?;
+    match mod_ids {
+        None => Ok(mods.into_iter().filter(|m| !m.is_missing).collect()),
+        Some(ids) => ids
+            .iter()
+            .map(|id| {
+                mods.iter()
+                    .find(|m| &m.id == id)
+                    .cloned()
+                    .ok_or_else(|| format!("Mod {} not found", id))
+            })
+            .collect(),
+    }
+}
+
+// Rewrite outdated hashes in the selected mods, or the whole library when
+// none are given. With `apply` false nothing is written. The original ini
+// files are kept, so `revert_mod_edits` undoes the fix per mod.
+pub async fn fix_hashes(
+    mapping_path: &str,
+    mod_ids: Option<Vec<String>>,
+    apply: bool,
+) -> Result<HashFixReport, String> {
+    let table = load_mapping_table(Path::new(mapping_path))?;
+    let mut report = HashFixReport {
+        versions: table.patches.iter().map(|p| p.version.clone()).collect(),
+        mods: Vec::new(),
+        failed: Vec::new(),
+    };
+
+    for m in selected_mods(mod_ids).await? {
+        let result = match plan_mod(&m, &table) {
+            Ok((fix, _)) if fix.changes.is_empty() => continue,
+            Ok((fix, edits)) if apply => write_fix(&m, &edits).await.map(|_| fix),
+            other => other.map(|(fix, _)| fix),
+        };
+        match result {
+            Ok(fix) => report.mods.push(fix),
+            Err(error) => report.failed.push(HashFixFailure {
+                mod_id: m.id.clone(),
+                title: m.title.clone(),
+                error,
+            }),
+        }
+    }
+    Ok(report)
+}
+
+// All files of a mod are fixed or none is; a mod is never left half-fixed
+async fn write_fix(m: &Mod, edits: &FileEdits) -> Result<(), String> {
+    let (root, _) = mod_ini_files(m)?;
+    edit_ini_files(m, &root, edits)?;
+    refresh_deployment(m).await
+}
@@ -0,0 +1,433 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// A small reader for the 3DMigoto ini dialect. Every entry keeps its line
+// number so callers can point at, or rewrite, single lines and leave the rest
+// of the file exactly as the author wrote it.
+
+// Statements that are valid without a `key = value` form
+const FLOW_KEYWORDS: [&str; 4] = ["if", "elif", "else", "endif"];
+const DECLARATION_KEYWORDS: [&str; 3] = ["global", "local", "persist"];
+
+#[derive(Debug, Clone)]
+pub struct IniEntry {
+    // As written; 3DMigoto compares keys case-insensitively
+    pub key: String,
+    pub value: String,
+    // 1-based
+    pub line: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct IniSection {
+    pub name: String,
+    pub line: usize,
+    pub entries: Vec<IniEntry>,
+}
+
+impl IniSection {
+    // First value for `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|e| e.key.eq_ignore_ascii_case(key))
+            .map(|e| e.value.as_str())
+    }
+
+    // Whether the section name starts with `prefix`, ignoring case
+    pub fn has_prefix(&self, prefix: &str) -> bool {
+        self.name
+            .get(..prefix.len())
+            .map(|p| p.eq_ignore_ascii_case(prefix))
+            .unwrap_or(false)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IniSyntaxError {
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IniDocument {
+    // Entries before the first section, where `namespace` lives
+    pub preamble: Vec<IniEntry>,
+    pub sections: Vec<IniSection>,
+    pub errors: Vec<IniSyntaxError>,
+}
+
+impl IniDocument {
+    pub fn namespace(&self) -> Option<&str> {
+        self.preamble
+            .iter()
+            .find(|e| e.key.eq_ignore_ascii_case("namespace"))
+            .map(|e| e.value.as_str())
+            .filter(|n| !n.is_empty())
+    }
+}
+
+// Read an ini file the way 3DMigoto does: bytes first, invalid UTF-8 replaced
+// rather than rejected, byte order mark dropped
+pub fn read_ini(path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    let text = String::from_utf8_lossy(&bytes);
+    Ok(text.trim_start_matches('\u{feff}').to_string())
+}
+
+fn first_word(line: &str) -> String {
+    line.split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+fn parse_entry(trimmed: &str, line: usize) -> Result<IniEntry, String> {
+    let word = first_word(trimmed);
+
+    // `if $a == 1` would otherwise split at the comparison
+    if FLOW_KEYWORDS.contains(&word.as_str()) {
+        return Ok(IniEntry {
+            key: word.clone(),
+            value: trimmed[word.len()..].trim().to_string(),
+            line,
+        });
+    }
+
+    if let Some((key, value)) = trimmed.split_once('=') {
+        let key = key.trim();
+        if key.is_empty() {
+            return Err("Missing key before '='".to_string());
+        }
+        return Ok(IniEntry {
+            key: key.to_string(),
+            value: value.trim().to_string(),
+            line,
+        });
+    }
+
+    // Bare declarations such as `global persist $swap`
+    if DECLARATION_KEYWORDS.contains(&word.as_str()) || word.starts_with('$') {
+        return Ok(IniEntry {
+            key: trimmed.to_string(),
+            value: String::new(),
+            line,
+        });
+    }
+
+    Err(format!("Expected 'key = value', found '{}'", trimmed))
+}
+
+// Report an `if` left open at the end of a section
+fn close_section(doc: &mut IniDocument, depth: usize) {
+    if depth > 0 {
+        if let Some(section) = doc.sections.last() {
+            doc.errors.push(IniSyntaxError {
+                line: section.line,
+                message: format!("[{}] has an 'if' without a matching 'endif'", section.name),
+            });
+        }
+    }
+}
+
+pub fn parse_ini(text: &str) -> IniDocument {
+    let mut doc = IniDocument::default();
+    let mut depth = 0usize;
+
+    for (index, raw) in text.lines().enumerate() {
+        let line = index + 1;
+        let trimmed = raw.trim();
+        if trimmed.is_empty() || trimmed.starts_with(';') {
+            continue;
+        }
+
+        if let Some(header) = trimmed.strip_prefix('[') {
+            close_section(&mut doc, depth);
+            depth = 0;
+
+            let name = match header.strip_suffix(']') {
+                Some(inner) => inner.trim().to_string(),
+                None => {
+                    doc.errors.push(IniSyntaxError {
+                        line,
+                        message: "Section header is missing its closing ']'".to_string(),
+                    });
+                    header.trim().to_string()
+                }
+            };
+            if name.is_empty() {
+                doc.errors.push(IniSyntaxError {
+                    line,
+                    message: "Section header has no name".to_string(),
+                });
+            }
+            doc.sections.push(IniSection {
+                name,
+                line,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let entry = match parse_entry(trimmed, line) {
+            Ok(entry) => entry,
+            Err(message) => {
+                doc.errors.push(IniSyntaxError { line, message });
+                continue;
+            }
+        };
+
+        match entry.key.as_str() {
+            "if" => depth += 1,
+            "endif" if depth == 0 => doc.errors.push(IniSyntaxError {
+                line,
+                message: "'endif' without a matching 'if'".to_string(),
+            }),
+            "endif" => depth -= 1,
+            "elif" | "else" if depth == 0 => doc.errors.push(IniSyntaxError {
+                line,
+                message: format!("'{}' outside of an 'if' block", entry.key),
+            }),
+            _ => {}
+        }
+
+        match doc.sections.last_mut() {
+            Some(section) => section.entries.push(entry),
+            None => doc.preamble.push(entry),
+        }
+    }
+
+    close_section(&mut doc, depth);
+    doc
+}
+
+// An entry as a line of ini text. Comments and spacing are not kept.
+pub fn render_entry(entry: &IniEntry) -> String {
+    let word = first_word(&entry.key);
+    if FLOW_KEYWORDS.contains(&word.as_str()) {
+        return format!("{} {}", entry.key, entry.value)
+            .trim_end()
+            .to_string();
+    }
+    if entry.value.is_empty()
+        && (DECLARATION_KEYWORDS.contains(&word.as_str()) || word.starts_with('$'))
+    {
+        return entry.key.clone();
+    }
+    format!("{} = {}", entry.key, entry.value)
+}
+
+// ===== Editing =====
+// Edits work on the raw bytes line by line: lines that aren't edited keep
+// their exact bytes, including comments in other encodings and CRLF endings.
+#[derive(Debug, Clone)]
+pub enum IniEdit {
+    // Replace the value of the `key = value` entry on `line`
+    SetValue { line: usize, value: String },
+    // Add `text` as a new line after `line`
+    InsertAfter { line: usize, text: String },
+    // Drop `line` entirely
+    RemoveLine { line: usize },
+}
+
+// Swap the value in `key = value`, keeping the spacing around it
+fn replace_value(raw: &str, value: &str) -> String {
+    let Some(eq) = raw.find('=') else {
+        return raw.to_string();
+    };
+    let (head, rest) = raw.split_at(eq + 1);
+    if rest.trim().is_empty() {
+        return format!("{} {}", head, value);
+    }
+    let leading = rest.len() - rest.trim_start().len();
+    let trailing = rest.len() - rest.trim_end().len();
+    format!(
+        "{}{}{}{}",
+        head,
+        &rest[..leading],
+        value,
+        &rest[rest.len() - trailing..]
+    )
+}
+
+fn inserted_after(edits: &[IniEdit], after: usize) -> Vec<&str> {
+    edits
+        .iter()
+        .filter_map(|edit| match edit {
+            IniEdit::InsertAfter { line, text } if *line == after => Some(text.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub fn apply_edits(bytes: &[u8], edits: &[IniEdit]) -> Vec<u8> {
+    let lines: Vec<&[u8]> = bytes.split(|b| *b == b'\n').collect();
+    let crlf = lines.iter().any(|l| l.ends_with(b"\r"));
+    let newline: &[u8] = if crlf { b"\r\n" } else { b"\n" };
+
+    let mut out = Vec::with_capacity(bytes.len());
+    for (index, raw) in lines.iter().enumerate() {
+        let line = index + 1;
+        let is_last = index + 1 == lines.len();
+        let removed = edits
+            .iter()
+            .any(|edit| matches!(edit, IniEdit::RemoveLine { line: l } if *l == line));
+
+        let new_value = edits.iter().rev().find_map(|edit| match edit {
+            IniEdit::SetValue { line: l, value } if *l == line => Some(value),
+            _ => None,
+        });
+        match new_value {
+            _ if removed => {}
+            Some(value) => {
+                let (content, cr) = match raw.strip_suffix(b"\r") {
+                    Some(content) => (content, true),
+                    None => (*raw, false),
+                };
+                let edited = replace_value(&String::from_utf8_lossy(content), value);
+                out.extend_from_slice(edited.as_bytes());
+                if cr {
+                    out.push(b'\r');
+                }
+            }
+            None => out.extend_from_slice(raw),
+        }
+        if !is_last && !removed {
+            out.push(b'\n');
+        }
+
+        let inserts = inserted_after(edits, line);
+        if inserts.is_empty() {
+            continue;
+        }
+        // The last line may have no newline of its own. An empty last line is
+        // only the end of a file that already ends with one.
+        if is_last && !removed && !raw.is_empty() {
+            out.extend_from_slice(newline);
+        }
+        for text in inserts {
+            out.extend_from_slice(text.as_bytes());
+            out.extend_from_slice(newline);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(text: &str, edits: &[IniEdit]) -> String {
+        String::from_utf8(apply_edits(text.as_bytes(), edits)).unwrap()
+    }
+
+    #[test]
+    fn parses_sections_entries_and_flow() {
+        let doc = parse_ini(
+            "namespace = Ellen\\Maid\n; comment\n[Constants]\nglobal persist $swap = 0\n\n[TextureOverrideBody]\nhash = 1a2b3c4d\nif $swap == 1\n  ps-t0 = ResourceA\nelse\n  ps-t0 = ResourceB\nendif\n",
+        );
+        assert_eq!(doc.namespace(), Some("Ellen\\Maid"));
+        assert!(doc.errors.is_empty(), "{:?}", doc.errors);
+        assert_eq!(doc.sections.len(), 2);
+        assert_eq!(doc.sections[0].entries[0].key, "global persist $swap");
+        assert_eq!(doc.sections[0].entries[0].value, "0");
+
+        let body = &doc.sections[1];
+        assert!(body.has_prefix("textureoverride"));
+        assert_eq!(body.get("HASH"), Some("1a2b3c4d"));
+        assert_eq!(body.line, 6);
+        let keys: Vec<&str> = body.entries.iter().map(|e| e.key.as_str()).collect();
+        assert_eq!(keys, ["hash", "if", "ps-t0", "else", "ps-t0", "endif"]);
+        assert_eq!(body.entries[1].value, "$swap == 1");
+        assert_eq!(body.entries[2].line, 9);
+    }
+
+    #[test]
+    fn reports_syntax_errors_with_lines() {
+        let doc = parse_ini("[Open\nif $a\n= 1\nbroken line\n[]\nendif\n");
+        let lines: Vec<usize> = doc.errors.iter().map(|e| e.line).collect();
+        assert_eq!(lines, [1, 3, 4, 1, 5, 6]);
+    }
+
+    #[test]
+    fn set_value_keeps_crlf_spacing_and_other_lines() {
+        let text = "[Constants]\r\n$swap  =  0 \r\n; caf\u{e9}\r\n";
+        let out = edit(
+            text,
+            &[IniEdit::SetValue {
+                line: 2,
+                value: "1".to_string(),
+            }],
+        );
+        assert_eq!(out, "[Constants]\r\n$swap  =  1 \r\n; caf\u{e9}\r\n");
+    }
+
+    #[test]
+    fn inserts_use_the_file_line_endings() {
+        let out = edit(
+            "[Constants]\r\n$a = 0\r\n",
+            &[IniEdit::InsertAfter {
+                line: 1,
+                text: "$b = 1".to_string(),
+            }],
+        );
+        assert_eq!(out, "[Constants]\r\n$b = 1\r\n$a = 0\r\n");
+    }
+
+    #[test]
+    fn insert_after_a_last_line_without_newline() {
+        let out = edit(
+            "[Constants]\n$a = 0",
+            &[IniEdit::InsertAfter {
+                line: 2,
+                text: "$b = 1".to_string(),
+            }],
+        );
+        assert_eq!(out, "[Constants]\n$a = 0\n$b = 1\n");
+    }
+
+    #[test]
+    fn insert_after_the_trailing_empty_line() {
+        let out = edit(
+            "[Constants]\n$a = 0\n",
+            &[IniEdit::InsertAfter {
+                line: 3,
+                text: "$b = 1".to_string(),
+            }],
+        );
+        assert_eq!(out, "[Constants]\n$a = 0\n$b = 1\n");
+        assert_eq!(
+            edit(
+                "",
+                &[IniEdit::InsertAfter {
+                    line: 1,
+                    text: "[Constants]".to_string(),
+                }],
+            ),
+            "[Constants]\n"
+        );
+    }
+
+    #[test]
+    fn remove_lines() {
+        let remove = |line| IniEdit::RemoveLine { line };
+        assert_eq!(edit("a\r\nb\r\nc\r\n", &[remove(2)]), "a\r\nc\r\n");
+        assert_eq!(edit("a\nb", &[remove(2)]), "a\n");
+        assert_eq!(
+            edit(
+                "a\nb\n",
+                &[
+                    remove(1),
+                    IniEdit::InsertAfter {
+                        line: 1,
+                        text: "x".to_string(),
+                    },
+                ],
+            ),
+            "x\nb\n"
+        );
+    }
+}
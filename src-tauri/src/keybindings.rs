@@ -0,0 +1,394 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use crate::activation::refresh_deployment;
+use crate::archive::safe_join;
+use crate::conflicts::{load_manifests, selected_mods};
+use crate::ini::{parse_ini, read_ini, IniEdit};
+use crate::manifest::{manifest_for, mod_ini_files, ModManifest};
+use crate::originals::edit_ini_files;
+use crate::storage::load_all_mods;
+use crate::Mod;
+
+// Modifiers 3DMigoto accepts in front of a key
+const MODIFIERS: [&str; 12] = [
+    "ctrl", "lctrl", "rctrl", "alt", "lalt", "ralt", "shift", "lshift", "rshift", "win", "lwin",
+    "rwin",
+];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyBindingEntry {
+    pub mod_id: String,
+    pub title: String,
+    pub ini_file: String,
+    pub section: String,
+    pub key: Option<String>,
+    pub back: Option<String>,
+    // cycle, toggle or hold
+    pub binding_type: String,
+    pub variables: Vec<String>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClashingBinding {
+    pub mod_id: String,
+    pub title: String,
+    pub ini_file: String,
+    pub section: String,
+    // As written in the ini
+    pub combo: String,
+    // Whether the combo is the binding's `back` key rather than its `key`
+    pub is_back: bool,
+}
+
+// One key combination used by more than one mod
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyClash {
+    pub key: String,
+    pub bindings: Vec<ClashingBinding>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyBindingReport {
+    pub bindings: Vec<KeyBindingEntry>,
+    pub clashes: Vec<KeyClash>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub key: String,
+    pub modifiers: BTreeSet<String>,
+    // `no_modifiers`, `no_ctrl` and friends: the key only fires without them
+    pub excludes_modifiers: bool,
+}
+
+impl KeyCombo {
+    // 3DMigoto fires a key without modifiers whatever modifiers are held,
+    // unless the binding excludes them
+    pub fn clashes_with(&self, other: &KeyCombo) -> bool {
+        self.key == other.key
+            && (self.modifiers == other.modifiers
+                || (self.modifiers.is_empty() && !self.excludes_modifiers)
+                || (other.modifiers.is_empty() && !other.excludes_modifiers))
+    }
+
+    pub fn display(&self) -> String {
+        let mut parts: Vec<&str> = self.modifiers.iter().map(String::as_str).collect();
+        parts.push(&self.key);
+        parts.join(" ")
+    }
+}
+
+fn normalize_key_name(token: &str) -> String {
+    let lower = token.to_lowercase();
+    let name = lower.strip_prefix("vk_").unwrap_or(&lower);
+    match name {
+        "control" => "ctrl",
+        "lcontrol" => "lctrl",
+        "rcontrol" => "rctrl",
+        "menu" => "alt",
+        "lmenu" => "lalt",
+        "rmenu" => "ralt",
+        other => other,
+    }
+    .to_string()
+}
+
+// `ctrl VK_RIGHT` -> key `right` with modifier `ctrl`
+pub fn parse_combo(value: &str) -> Option<KeyCombo> {
+    let mut combo = KeyCombo {
+        key: String::new(),
+        modifiers: BTreeSet::new(),
+        excludes_modifiers: false,
+    };
+
+    for token in value.split_whitespace() {
+        let name = normalize_key_name(token);
+        if name.starts_with("no_") {
+            combo.excludes_modifiers = true;
+        } else if MODIFIERS.contains(&name.as_str()) {
+            combo.modifiers.insert(name);
+        } else {
+            combo.key = name;
+        }
+    }
+
+    (!combo.key.is_empty()).then_some(combo)
+}
+
+fn binding_entries(mods: &[&Mod], manifests: &[ModManifest]) -> Vec<KeyBindingEntry> {
+    let mut entries = Vec::new();
+    for manifest in manifests {
+        let Some(m) = mods.iter().find(|m| m.id == manifest.mod_id) else {
+            continue;
+        };
+        for binding in &manifest.key_bindings {
+            entries.push(KeyBindingEntry {
+                mod_id: m.id.clone(),
+                title: m.title.clone(),
+                ini_file: binding.ini_file.clone(),
+                section: binding.section.clone(),
+                key: binding.key.clone(),
+                back: binding.back.clone(),
+                binding_type: binding
+                    .binding_type
+                    .as_deref()
+                    .unwrap_or("hold")
+                    .to_lowercase(),
+                variables: binding.variables.iter().map(|v| v.name.clone()).collect(),
+                line: binding.line,
+            });
+        }
+    }
+    entries
+}
+
+// Group every `key` and `back` combo with the ones it fires together with.
+// Bindings within a single mod are deliberate and never reported.
+pub fn find_key_clashes(bindings: &[KeyBindingEntry]) -> Vec<KeyClash> {
+    let mut combos: Vec<(KeyCombo, ClashingBinding)> = Vec::new();
+    for b in bindings {
+        for (value, is_back) in [(&b.key, false), (&b.back, true)] {
+            let Some(combo) = value.as_deref().and_then(parse_combo) else {
+                continue;
+            };
+            combos.push((
+                combo,
+                ClashingBinding {
+                    mod_id: b.mod_id.clone(),
+                    title: b.title.clone(),
+                    ini_file: b.ini_file.clone(),
+                    section: b.section.clone(),
+                    combo: value.clone().unwrap_or_default(),
+                    is_back,
+                },
+            ));
+        }
+    }
+
+    let mut clashes: Vec<KeyClash> = Vec::new();
+    let mut reported = vec![false; combos.len()];
+    for i in 0..combos.len() {
+        if reported[i] {
+            continue;
+        }
+        let group: Vec<usize> = (0..combos.len())
+            .filter(|&j| !reported[j] && combos[i].0.clashes_with(&combos[j].0))
+            .collect();
+        let distinct: BTreeSet<&str> = group.iter().map(|&j| combos[j].1.mod_id.as_str()).collect();
+        if distinct.len() < 2 {
+            continue;
+        }
+        for &j in &group {
+            reported[j] = true;
+        }
+        clashes.push(KeyClash {
+            key: combos[i].0.display(),
+            bindings: group.iter().map(|&j| combos[j].1.clone()).collect(),
+        });
+    }
+    clashes
+}
+
+// Every key binding of the active mods, or of a preset's mods, and the keys
+// more than one of them uses
+pub async fn list_key_bindings(preset_id: Option<&str>) -> Result<KeyBindingReport, String> {
+    let mods = selected_mods(preset_id).await?;
+    let selected: Vec<&Mod> = mods.iter().collect();
+    let manifests = load_manifests(&selected).await;
+
+    let bindings = binding_entries(&selected, &manifests);
+    let clashes = find_key_clashes(&bindings);
+    Ok(KeyBindingReport { bindings, clashes })
+}
+
+// ===== Remapping =====
+// New keys for one `[Key...]` section; a missing value leaves that key as is
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRemap {
+    pub ini_file: String,
+    pub section: String,
+    pub key: Option<String>,
+    pub back: Option<String>,
+}
+
+fn remap_edits(text: &str, remaps: &[&KeyRemap]) -> Result<Vec<IniEdit>, String> {
+    let doc = parse_ini(text);
+    let mut edits = Vec::new();
+
+    for remap in remaps {
+        let section = doc
+            .sections
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case(&remap.section))
+            .ok_or_else(|| format!("[{}] not found in {}", remap.section, remap.ini_file))?;
+
+        for (name, value) in [("key", &remap.key), ("back", &remap.back)] {
+            let Some(value) = value.as_deref().map(str::trim).filter(|v| !v.is_empty()) else {
+                continue;
+            };
+            if parse_combo(value).is_none() {
+                return Err(format!("'{}' is not a valid key binding", value));
+            }
+
+            let existing = section
+                .entries
+                .iter()
+                .find(|e| e.key.eq_ignore_ascii_case(name));
+            match existing {
+                Some(entry) => edits.push(IniEdit::SetValue {
+                    line: entry.line,
+                    value: value.to_string(),
+                }),
+                None => {
+                    // A new `back` goes right below the section's `key`
+                    let anchor = section
+                        .entries
+                        .iter()
+                        .find(|e| e.key.eq_ignore_ascii_case("key"))
+                        .map(|e| e.line)
+                        .unwrap_or(section.line);
+                    edits.push(IniEdit::InsertAfter {
+                        line: anchor,
+                        text: format!("{} = {}", name, value),
+                    });
+                }
+            }
+        }
+    }
+    Ok(edits)
+}
+
+// Rewrite `key =` / `back =` values in the library copy of a mod. Every
+// change is checked before any file is written, and each ini file's original
+// is kept so the edits can be reverted.
+pub async fn remap_key_bindings(
+    mod_id: &str,
+    remaps: Vec<KeyRemap>,
+) -> Result<ModManifest, String> {
+    let m = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or("Mod not found")?;
+    let (root, files) = mod_ini_files(&m)?;
+
+    let mut by_file: BTreeMap<&str, Vec<&KeyRemap>> = BTreeMap::new();
+    for remap in &remaps {
+        by_file
+            .entry(remap.ini_file.as_str())
+            .or_default()
+            .push(remap);
+    }
+
+    let mut planned: Vec<(PathBuf, Vec<IniEdit>)> = Vec::new();
+    for (ini_file, remaps) in by_file {
+        let path = safe_join(&root, ini_file)
+            .filter(|p| files.contains(p))
+            .ok_or_else(|| format!("{} is not an ini file of this mod", ini_file))?;
+        let text = read_ini(&path).map_err(|e| format!("Failed to read {}: {}", ini_file, e))?;
+        planned.push((path, remap_edits(&text, &remaps)?));
+    }
+
+    edit_ini_files(&m, &root, &planned)?;
+
+    refresh_deployment(&m).await?;
+    manifest_for(&m).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ini::apply_edits;
+
+    fn combo(value: &str) -> KeyCombo {
+        parse_combo(value).unwrap()
+    }
+
+    fn remap(section: &str, key: Option<&str>, back: Option<&str>) -> KeyRemap {
+        KeyRemap {
+            ini_file: "mod.ini".to_string(),
+            section: section.to_string(),
+            key: key.map(str::to_string),
+            back: back.map(str::to_string),
+        }
+    }
+
+    fn remapped(text: &str, remaps: &[KeyRemap]) -> Result<String, String> {
+        let remaps: Vec<&KeyRemap> = remaps.iter().collect();
+        let edits = remap_edits(text, &remaps)?;
+        Ok(String::from_utf8(apply_edits(text.as_bytes(), &edits)).unwrap())
+    }
+
+    #[test]
+    fn parses_keys_and_modifiers() {
+        let parsed = combo("VK_CONTROL shift VK_RIGHT");
+        assert_eq!(parsed.key, "right");
+        assert_eq!(
+            parsed.modifiers.iter().collect::<Vec<_>>(),
+            ["ctrl", "shift"]
+        );
+        assert!(!parsed.excludes_modifiers);
+        assert_eq!(parsed.display(), "ctrl shift right");
+
+        let parsed = combo("no_modifiers VK_F6");
+        assert_eq!(parsed.key, "f6");
+        assert!(parsed.excludes_modifiers && parsed.modifiers.is_empty());
+
+        assert_eq!(
+            combo("lmenu x").modifiers.iter().collect::<Vec<_>>(),
+            ["lalt"]
+        );
+        assert!(parse_combo("ctrl alt").is_none());
+        assert!(parse_combo("  ").is_none());
+    }
+
+    #[test]
+    fn a_bare_key_clashes_unless_it_excludes_modifiers() {
+        assert!(combo("x").clashes_with(&combo("X")));
+        assert!(combo("x").clashes_with(&combo("ctrl x")));
+        assert!(combo("ctrl x").clashes_with(&combo("x")));
+        assert!(!combo("ctrl x").clashes_with(&combo("alt x")));
+        assert!(!combo("no_ctrl x").clashes_with(&combo("ctrl x")));
+        assert!(combo("no_ctrl x").clashes_with(&combo("x")));
+        assert!(!combo("x").clashes_with(&combo("y")));
+    }
+
+    #[test]
+    fn remap_sets_existing_values_and_adds_back_below_key() {
+        let text = "[KeySwap]\r\nkey = VK_UP\r\ntype = cycle\r\n\r\n[KeyGlow]\r\ntype = toggle\r\n";
+        let out = remapped(
+            text,
+            &[
+                remap("keyswap", Some(" ctrl VK_DOWN "), Some("ctrl VK_UP")),
+                remap("KeyGlow", Some("g"), None),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            out,
+            "[KeySwap]\r\nkey = ctrl VK_DOWN\r\nback = ctrl VK_UP\r\ntype = cycle\r\n\r\n[KeyGlow]\r\nkey = g\r\ntype = toggle\r\n"
+        );
+    }
+
+    #[test]
+    fn remap_rejects_unknown_sections_and_invalid_keys() {
+        let text = "[KeySwap]\nkey = VK_UP\n";
+        let err = remapped(text, &[remap("KeyOther", Some("x"), None)]).unwrap_err();
+        assert!(err.contains("[KeyOther] not found"), "{}", err);
+        let err = remapped(text, &[remap("KeySwap", Some("ctrl"), None)]).unwrap_err();
+        assert!(err.contains("not a valid key binding"), "{}", err);
+        // Empty values leave the key alone
+        assert_eq!(
+            remapped(text, &[remap("KeySwap", Some(" "), None)]).unwrap(),
+            text
+        );
+    }
+}
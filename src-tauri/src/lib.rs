@@ -1,10 +1,40 @@
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+mod activation;
+mod archive;
+mod classify;
+mod conflicts;
+mod dependencies;
+mod exclusive;
+mod hashfix;
+mod ini;
+mod keybindings;
+mod library;
+mod links;
+mod lint;
+mod load_order;
+mod manifest;
+mod merge;
+mod originals;
+mod presets;
+mod reconcile;
+mod storage;
+mod unmerge;
+mod variables;
+mod variants;
+
+use activation::{activator_for, Activator};
+use links::{build_link_name, link_path_for};
+use storage::{
+    load_all_mods, load_all_presets, load_settings, remove_mod_metadata, save_all_mods,
+    save_mod_metadata, save_settings,
+};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Mod {
     pub id: String,
@@ -16,6 +46,20 @@ pub struct Mod {
     pub character: Option<String>,
     pub file_path: String,
     pub original_name: String,
+    #[serde(default)]
+    pub is_missing: bool, // Set by a library rescan when file_path no longer exists
+    #[serde(default)]
+    pub link_name: String, // Folder name of the link in zzmi/mods, see links::build_link_name
+    #[serde(default)]
+    pub variants: Vec<String>, // Sub-folders of a multi-variant pack, see variants::find_variants
+    #[serde(default)]
+    pub selected_variant: Option<String>, // The one variant deployed to zzmi/mods; None deploys everything
+    #[serde(default)]
+    pub group: Option<String>, // Custom slot used instead of the character, see exclusive::slot_of
+    #[serde(default)]
+    pub dependencies: Vec<dependencies::Dependency>, // Mods and ZZMI files this mod needs to work
+    #[serde(default)]
+    pub load_order: Option<u32>, // Lower loads first; None loads after every numbered mod, see load_order
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,11 +69,29 @@ pub struct Preset {
     pub created_at: String,
     pub updated_at: String,
     pub mod_ids: Vec<String>,
+    #[serde(default)]
+    pub variables: Vec<PresetVariable>, // Saved toggle states restored with the preset
+    #[serde(default)]
+    pub load_order: BTreeMap<String, u32>, // Mod id -> load order the preset puts it in
+}
+
+// A mod's persisted `$variable` value as captured in a preset; None means the
+// mod's own default
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresetVariable {
+    pub mod_id: String,
+    pub ini_file: String,
+    pub name: String,
+    pub value: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AppSettings {
     pub zzmi_mods_path: Option<String>, // Path to zzmi/mods folder for active mods
+    #[serde(default)]
+    pub activation_mode: activation::ActivationMode, // How active mods are put into zzmi/mods
+    #[serde(default)]
+    pub exclusive_slots: Vec<String>, // Characters or groups that allow only one active mod
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +103,18 @@ pub struct ModStats {
     pub presets: usize,
 }
 
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToggleResult {
+    pub is_active: bool,
+    // Hash overrides the activation now shares with other active mods
+    pub conflicts: Vec<conflicts::HashConflict>,
+    // Mods of the same exclusive slot that were deactivated to make room
+    pub deactivated: Vec<String>,
+    // Dependencies that were activated along with the mod
+    pub activated: Vec<String>,
+}
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -60,7 +134,7 @@ async fn install_mod(
     character: Option<String>,
     description: Option<String>,
     thumbnail: Option<String>,
-) -> Result<Mod, String> {
+) -> Result<lint::AddedMod, String> {
     println!(
         "Installing mod: title={}, filePath={}, character={:?}",
         title, filePath, character
@@ -76,22 +150,107 @@ async fn install_mod(
         return Err(error);
     }
 
-    // Validate mod structure
-    validate_mod_structure(Path::new(&filePath))?;
+    // Archives are unpacked into a staging folder first so we can locate the
+    // real mod root inside any wrapper folders before it enters the library
+    let source = Path::new(&filePath);
+    let staging_dir = if archive::is_supported_archive(source) {
+        let dir = get_app_config_dir()?
+            .join("tmp")
+            .join(Uuid::new_v4().to_string());
+        println!("Extracting archive {} to {}", filePath, dir.display());
+        Some(dir)
+    } else {
+        None
+    };
 
-    // Use app-managed mods folder
-    let app_data_path = get_app_config_dir()?
-        .join("mods")
-        .to_string_lossy()
-        .to_string();
+    let result =
+        copy_mod_into_library(source, staging_dir.as_deref(), character.as_deref(), &title);
 
-    // Determine storage folder based on character
-    let storage_folder = if let Some(ref char_name) = character {
-        format!("{}/characters/{}", app_data_path, char_name)
+    if let Some(dir) = staging_dir.filter(|d| d.exists()) {
+        if let Err(e) = fs::remove_dir_all(&dir) {
+            println!("Warning: failed to clean up staging folder {}: {}", dir.display(), e);
+        }
+    }
+
+    let (destination_path, original_name, character) = result?;
+
+    // Packs with several variants side by side start out with the first one
+    let variants = variants::find_variants(Path::new(&destination_path));
+    if !variants.is_empty() {
+        println!("Found variants {:?}; deploying {}", variants, variants[0]);
+    }
+
+    let mut new_mod = Mod {
+        id: mod_id,
+        title,
+        description,
+        thumbnail,
+        is_active: false,
+        date_added: now.to_rfc3339(),
+        character,
+        file_path: destination_path,
+        original_name,
+        is_missing: false,
+        link_name: String::new(),
+        selected_variant: variants.first().cloned(),
+        variants,
+        group: None,
+        dependencies: Vec::new(),
+        load_order: None,
+    };
+    new_mod.link_name = build_link_name(&new_mod);
+
+    // Save mod metadata
+    save_mod_metadata(&new_mod).await?;
+
+    // Problems that would otherwise only show up in game
+    Ok(lint::lint_added(new_mod))
+}
+
+// Copy a mod folder (or the extracted contents of an archive) into the
+// app-managed library and return its destination path and original name
+fn copy_mod_into_library(
+    source: &Path,
+    staging_dir: Option<&Path>,
+    character: Option<&str>,
+    title: &str,
+) -> Result<(String, String, Option<String>), String> {
+    let (mod_root, original_name) = if let Some(staging) = staging_dir {
+        archive::extract_archive(source, staging)?;
+        let root = archive::find_mod_root(staging)?;
+        let name = if root == staging {
+            archive::archive_stem(source)
+        } else {
+            root.file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown")
+                .to_string()
+        };
+        (root, name)
     } else {
-        format!("{}/othermods", app_data_path)
+        let name = source
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        (source.to_path_buf(), name)
     };
 
+    // Validate mod structure
+    validate_mod_structure(&mod_root)?;
+
+    // Mods installed without a character are filed under the one they
+    // target when the classifier is sure of it
+    let character = match character {
+        Some(c) => Some(c.to_string()),
+        None => classify::detect_character(&mod_root, &[title, &original_name]),
+    };
+    if let Some(c) = &character {
+        println!("Storing mod under character {}", c);
+    }
+
+    let storage_folder = storage_folder_for(character.as_deref())?;
+
     // Create storage directory if it doesn't exist
     println!("Creating storage directory: {}", storage_folder);
     fs::create_dir_all(&storage_folder).map_err(|e| {
@@ -100,44 +259,32 @@ async fn install_mod(
         error
     })?;
 
-    // Get original filename and create destination path
-    let original_name = Path::new(&filePath)
-        .file_name()
-        .and_then(|n| n.to_str())
-        .unwrap_or("unknown")
-        .to_string();
-
-    let destination_path = format!("{}/{}", storage_folder, original_name);
-    println!("Copying from {} to {}", filePath, destination_path);
+    // A mod of the same name may already be there; never copy over it
+    let destination_path = library::unique_destination(&storage_folder, &original_name);
+    println!("Copying from {} to {}", mod_root.display(), destination_path);
 
     // Copy folder to storage location
-    copy_dir_all(&filePath, &destination_path).map_err(|e| {
+    copy_dir_all(&mod_root, &destination_path).map_err(|e| {
         let error = format!("Failed to copy mod folder: {}", e);
         println!("Error: {}", error);
         error
     })?;
 
-    let new_mod = Mod {
-        id: mod_id,
-        title,
-        description,
-        thumbnail,
-        is_active: false,
-        date_added: now.to_rfc3339(),
-        character,
-        file_path: destination_path,
-        original_name: original_name.clone(),
-    };
-
-    // Save mod metadata
-    save_mod_metadata(&new_mod).await?;
+    Ok((destination_path, original_name, character))
+}
 
-    Ok(new_mod)
+// Put back the mods that made way for one that then failed to go in
+fn restore_displaced(activator: &dyn Activator, zzmi_path: &str, displaced: &[Mod]) {
+    for other in displaced {
+        if let Err(e) = activator.activate(zzmi_path, other) {
+            println!("Failed to restore {}: {}", other.title, e);
+        }
+    }
 }
 
 #[tauri::command]
 #[allow(non_snake_case)]
-async fn toggle_mod_active(modId: String) -> Result<bool, String> {
+async fn toggle_mod_active(modId: String) -> Result<ToggleResult, String> {
     println!("Toggling mod active: modId={}", modId);
 
     let mut mods = load_all_mods().await?;
@@ -146,41 +293,137 @@ async fn toggle_mod_active(modId: String) -> Result<bool, String> {
         .position(|m| m.id == modId)
         .ok_or("Mod not found")?;
 
-    let mod_ref = &mut mods[mod_index];
     let settings = load_settings().await?;
     let zzmi_path = settings
         .zzmi_mods_path
+        .clone()
         .ok_or("ZZMI mods path not configured. Please set it in settings.")?;
+    let activator = activator_for(settings.activation_mode);
 
     // Create zzmi mods directory if it doesn't exist
     fs::create_dir_all(&zzmi_path)
         .map_err(|e| format!("Failed to create ZZMI mods directory: {}", e))?;
 
-    let zzmi_file_path = format!("{}/{}", zzmi_path, mod_ref.original_name);
+    // Only one mod of an exclusive slot stays active, and what the mod needs
+    // goes in before it. ZZMI is changed first and the records all at once
+    // after, so either can be put back when the other fails.
+    let mut dependencies: Vec<Mod> = Vec::new();
+    let mut displaced: Vec<Mod> = Vec::new();
+    if !mods[mod_index].is_active {
+        dependencies =
+            dependencies::activation_dependencies(&settings, &zzmi_path, &mods, &mods[mod_index])?
+                .into_iter()
+                .cloned()
+                .collect();
+        displaced = exclusive::displaced_by(&settings, &mods, &mods[mod_index])
+            .into_iter()
+            .cloned()
+            .collect();
+    }
+    for (done, other) in displaced.iter().enumerate() {
+        println!("Deactivating {} to make room in its slot", other.title);
+        if let Err(e) = activator.deactivate(&zzmi_path, other) {
+            restore_displaced(activator.as_ref(), &zzmi_path, &displaced[..done]);
+            return Err(format!("Failed to remove {} from ZZMI: {}", other.title, e));
+        }
+    }
+    for (done, dependency) in dependencies.iter().enumerate() {
+        println!(
+            "Activating {}, which {} needs",
+            dependency.title, mods[mod_index].title
+        );
+        if let Err(e) = activator.activate(&zzmi_path, dependency) {
+            for activated in &dependencies[..done] {
+                let _ = activator.deactivate(&zzmi_path, activated);
+            }
+            restore_displaced(activator.as_ref(), &zzmi_path, &displaced);
+            return Err(format!(
+                "Failed to add {}, which {} needs, to ZZMI: {}",
+                dependency.title, mods[mod_index].title, e
+            ));
+        }
+    }
+    let mod_ref = &mut mods[mod_index];
+
+    let zzmi_file_path = link_path_for(&zzmi_path, mod_ref);
     println!("ZZMI path: {}, File path: {}", zzmi_path, zzmi_file_path);
 
     if mod_ref.is_active {
-        // Deactivate: Remove symlink from zzmi/mods folder
-        println!("Deactivating mod: removing symlink from {}", zzmi_file_path);
-        remove_symlink(Path::new(&zzmi_file_path))
-            .map_err(|e| format!("Failed to remove mod symlink from ZZMI: {}", e))?;
+        // Deactivate: Take the mod out of the zzmi/mods folder
+        println!("Deactivating mod: removing {}", zzmi_file_path);
+        if !activator
+            .deactivate(&zzmi_path, mod_ref)
+            .map_err(|e| format!("Failed to remove mod from ZZMI: {}", e))?
+        {
+            println!("No managed mod found at {}", zzmi_file_path);
+        }
         mod_ref.is_active = false;
     } else {
-        // Activate: Create symlink to zzmi/mods folder
+        // Activate: Put the mod into the zzmi/mods folder
         println!(
-            "Activating mod: creating symlink from {} to {}",
-            mod_ref.file_path, zzmi_file_path
+            "Activating mod: deploying {} to {} ({:?})",
+            mod_ref.file_path, zzmi_file_path, settings.activation_mode
         );
-        create_symlink(Path::new(&mod_ref.file_path), Path::new(&zzmi_file_path))
-            .map_err(|e| format!("Failed to create mod symlink to ZZMI: {}", e))?;
+        if let Err(e) = activator.activate(&zzmi_path, mod_ref) {
+            for dependency in &dependencies {
+                let _ = activator.deactivate(&zzmi_path, dependency);
+            }
+            restore_displaced(activator.as_ref(), &zzmi_path, &displaced);
+            return Err(format!("Failed to add mod to ZZMI: {}", e));
+        }
         mod_ref.is_active = true;
         println!("Mod activated successfully");
     }
 
     // Save updated mod metadata
-    save_mod_metadata(mod_ref).await?;
+    for other in displaced.iter_mut() {
+        other.is_active = false;
+    }
+    for dependency in dependencies.iter_mut() {
+        dependency.is_active = true;
+    }
+    let changed: Vec<Mod> = std::iter::once(mod_ref.clone())
+        .chain(displaced.iter().cloned())
+        .chain(dependencies.iter().cloned())
+        .collect();
+    if let Err(e) = save_all_mods(&changed).await {
+        // Take ZZMI back to what the records still say
+        if mod_ref.is_active {
+            let _ = activator.deactivate(&zzmi_path, mod_ref);
+            for dependency in &dependencies {
+                let _ = activator.deactivate(&zzmi_path, dependency);
+            }
+            restore_displaced(activator.as_ref(), &zzmi_path, &displaced);
+        } else if let Err(restore) = activator.activate(&zzmi_path, mod_ref) {
+            println!("Failed to restore {}: {}", mod_ref.title, restore);
+        }
+        return Err(e);
+    }
+    for m in mods.iter_mut() {
+        if let Some(saved) = changed.iter().find(|c| c.id == m.id) {
+            m.is_active = saved.is_active;
+        }
+    }
 
-    Ok(mod_ref.is_active)
+    let mut result = ToggleResult {
+        is_active: mods[mod_index].is_active,
+        conflicts: Vec::new(),
+        deactivated: displaced.into_iter().map(|m| m.id).collect(),
+        activated: dependencies.into_iter().map(|m| m.id).collect(),
+    };
+    if result.is_active {
+        let toggled = &mods[mod_index];
+        let others: Vec<&Mod> = mods.iter().filter(|m| m.is_active).collect();
+        result.conflicts = conflicts::conflicts_introduced(&others, &[toggled]).await;
+        for conflict in &result.conflicts {
+            println!(
+                "{} overrides hash {} together with another active mod",
+                toggled.title, conflict.hash
+            );
+        }
+    }
+
+    Ok(result)
 }
 
 #[tauri::command]
@@ -227,14 +470,22 @@ async fn delete_mod(modId: String) -> Result<(), String> {
 
     let mod_to_delete = &mods[mod_index];
 
-    // Remove from zzmi/mods if active
-    if mod_to_delete.is_active {
-        let settings = load_settings().await?;
-        if let Some(zzmi_path) = settings.zzmi_mods_path {
-            let zzmi_file_path = format!("{}/{}", zzmi_path, mod_to_delete.original_name);
-            remove_symlink(Path::new(&zzmi_file_path))
-                .map_err(|e| format!("Failed to remove mod symlink from ZZMI: {}", e))?;
-        }
+    let dependents = dependencies::dependents_of(&mods, &modId);
+    if !dependents.is_empty() {
+        let titles: Vec<&str> = dependents.iter().map(|m| m.title.as_str()).collect();
+        return Err(format!(
+            "{} can't be deleted: {} depend on it",
+            mod_to_delete.title,
+            titles.join(", ")
+        ));
+    }
+
+    // Remove from zzmi/mods, including copies kept there while inactive
+    let settings = load_settings().await?;
+    if let Some(zzmi_path) = settings.zzmi_mods_path {
+        activator_for(settings.activation_mode)
+            .purge(&zzmi_path, mod_to_delete)
+            .map_err(|e| format!("Failed to remove mod from ZZMI: {}", e))?;
     }
 
     // Remove from storage location
@@ -250,6 +501,9 @@ async fn delete_mod(modId: String) -> Result<(), String> {
 
     // Remove metadata
     remove_mod_metadata(&modId).await?;
+    if let Err(e) = originals::discard_originals(&modId) {
+        println!("Failed to remove ini backups of {}: {}", modId, e);
+    }
 
     Ok(())
 }
@@ -278,8 +532,19 @@ async fn get_settings() -> Result<AppSettings, String> {
 }
 
 #[tauri::command]
-async fn update_settings(settings: AppSettings) -> Result<(), String> {
-    save_settings(&settings).await
+async fn update_settings(mut settings: AppSettings) -> Result<(), String> {
+    // The mode only changes through set_activation_mode, which moves the
+    // active mods over to it, and the slots through set_exclusive_slots
+    let stored = load_settings().await?;
+    settings.activation_mode = stored.activation_mode;
+    settings.exclusive_slots = stored.exclusive_slots;
+    save_settings(&settings).await?;
+
+    // The ZZMI folder may have changed; bring the links in line with it
+    if let Err(e) = reconcile::reconcile_active_state(true).await {
+        println!("Failed to reconcile active mods: {}", e);
+    }
+    Ok(())
 }
 
 #[tauri::command]
@@ -302,95 +567,39 @@ async fn select_mod_folder() -> Result<Option<String>, String> {
     Ok(folder.map(|p| p.to_string_lossy().to_string()))
 }
 
-// Helper functions for file operations
-async fn load_settings() -> Result<AppSettings, String> {
-    let settings_path = get_app_config_dir()?.join("settings.json");
-
-    if !settings_path.exists() {
-        // Create default settings with app-managed paths
-        let default_settings = AppSettings {
-            zzmi_mods_path: None,
-        };
-
-        // Save default settings
-        save_settings(&default_settings).await?;
-        return Ok(default_settings);
-    }
+#[tauri::command]
+async fn select_mod_archive() -> Result<Option<String>, String> {
+    use rfd::FileDialog;
 
-    let content = fs::read_to_string(&settings_path)
-        .map_err(|e| format!("Failed to read settings: {}", e))?;
+    let file = FileDialog::new()
+        .set_title("Select Mod Archive")
+        .add_filter("Mod archives", &["zip", "7z", "rar"])
+        .pick_file();
 
-    serde_json::from_str(&content).map_err(|e| format!("Failed to parse settings: {}", e))
+    Ok(file.map(|p| p.to_string_lossy().to_string()))
 }
 
-async fn save_settings(settings: &AppSettings) -> Result<(), String> {
-    let config_dir = get_app_config_dir()?;
-    fs::create_dir_all(&config_dir)
-        .map_err(|e| format!("Failed to create config directory: {}", e))?;
-
-    let settings_path = config_dir.join("settings.json");
-    let content = serde_json::to_string_pretty(settings)
-        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-
-    fs::write(&settings_path, content).map_err(|e| format!("Failed to write settings: {}", e))
+// Helper functions for file operations
+fn get_app_config_dir() -> Result<PathBuf, String> {
+    dirs::config_dir()
+        .map(|p| p.join("aether-manager"))
+        .ok_or("Failed to get app config directory".to_string())
 }
 
-async fn load_all_mods() -> Result<Vec<Mod>, String> {
+// App-managed folder a mod for `character` is stored in
+fn storage_folder_for(character: Option<&str>) -> Result<String, String> {
+    // Use app-managed mods folder
     let app_data_path = get_app_config_dir()?
         .join("mods")
         .to_string_lossy()
         .to_string();
-    let mods_db_path = Path::new(&app_data_path).join("mods.json");
-
-    if !mods_db_path.exists() {
-        return Ok(vec![]);
-    }
-
-    let content = fs::read_to_string(&mods_db_path)
-        .map_err(|e| format!("Failed to read mods database: {}", e))?;
 
-    serde_json::from_str(&content).map_err(|e| format!("Failed to parse mods database: {}", e))
-}
-
-async fn save_mod_metadata(new_mod: &Mod) -> Result<(), String> {
-    let mut mods = load_all_mods().await.unwrap_or_default();
-
-    // Update existing or add new
-    if let Some(index) = mods.iter().position(|m| m.id == new_mod.id) {
-        mods[index] = new_mod.clone();
+    // Determine storage folder based on character
+    Ok(if let Some(char_name) = character {
+        format!("{}/characters/{}", app_data_path, char_name)
     } else {
-        mods.push(new_mod.clone());
-    }
-
-    save_all_mods(&mods).await
-}
-
-async fn remove_mod_metadata(mod_id: &str) -> Result<(), String> {
-    let mut mods = load_all_mods().await.unwrap_or_default();
-    mods.retain(|m| m.id != mod_id);
-    save_all_mods(&mods).await
-}
-
-async fn save_all_mods(mods: &[Mod]) -> Result<(), String> {
-    let app_data_path = get_app_config_dir()?
-        .join("mods")
-        .to_string_lossy()
-        .to_string();
-
-    fs::create_dir_all(&app_data_path)
-        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
-
-    let mods_db_path = Path::new(&app_data_path).join("mods.json");
-    let content = serde_json::to_string_pretty(mods)
-        .map_err(|e| format!("Failed to serialize mods: {}", e))?;
-
-    fs::write(&mods_db_path, content).map_err(|e| format!("Failed to write mods database: {}", e))
-}
-
-fn get_app_config_dir() -> Result<PathBuf, String> {
-    dirs::config_dir()
-        .map(|p| p.join("aether-manager"))
-        .ok_or("Failed to get app config directory".to_string())
+        format!("{}/othermods", app_data_path)
+    })
 }
 
 fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
@@ -407,48 +616,43 @@ fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result
     Ok(())
 }
 
-// Create symlink (cross-platform)
-fn create_symlink(src: &Path, dst: &Path) -> std::io::Result<()> {
-    // Remove existing file/dir if it exists
-    if dst.exists() {
-        if dst.is_dir() {
-            fs::remove_dir_all(dst)?;
+// Whether every file under `src` is in `dst` with the same size
+fn is_complete_copy(src: &Path, dst: &Path) -> std::io::Result<bool> {
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        let complete = if entry.file_type()?.is_dir() {
+            is_complete_copy(&entry.path(), &target)?
         } else {
-            fs::remove_file(dst)?;
+            let len = entry.metadata()?.len();
+            fs::metadata(&target).is_ok_and(|m| m.len() == len)
+        };
+        if !complete {
+            return Ok(false);
         }
     }
-    
-    // Create parent directory
+    Ok(true)
+}
+
+// Move a folder. Only a move across volumes falls back to copy + delete, and
+// the source is only deleted once the copy is known to be complete.
+fn move_dir(src: &Path, dst: &Path) -> std::io::Result<()> {
     if let Some(parent) = dst.parent() {
         fs::create_dir_all(parent)?;
     }
-    
-    #[cfg(windows)]
-    {
-        if src.is_dir() {
-            std::os::windows::fs::symlink_dir(src, dst)
-        } else {
-            std::os::windows::fs::symlink_file(src, dst)
-        }
-    }
-    
-    #[cfg(unix)]
-    {
-        std::os::unix::fs::symlink(src, dst)
+    match fs::rename(src, dst) {
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {}
+        result => return result,
     }
-}
 
-// Remove symlink safely
-fn remove_symlink(path: &Path) -> std::io::Result<()> {
-    if path.exists() {
-        if path.is_dir() {
-            fs::remove_dir_all(path)
-        } else {
-            fs::remove_file(path)
-        }
-    } else {
-        Ok(())
+    let copied = copy_dir_all(src, dst).and_then(|_| is_complete_copy(src, dst));
+    if !matches!(copied, Ok(true)) {
+        let _ = fs::remove_dir_all(dst);
+        return Err(copied.err().unwrap_or_else(|| {
+            std::io::Error::other(format!("copy of {} is incomplete", src.display()))
+        }));
     }
+    fs::remove_dir_all(src)
 }
 
 // Validate mod structure
@@ -498,29 +702,237 @@ fn validate_mod_structure(mod_path: &Path) -> Result<(), String> {
     Ok(())
 }
 
-// ===== Presets persistence =====
-async fn presets_db_path() -> Result<PathBuf, String> {
-    let base = get_app_config_dir()?;
-    let dir = base.join("mods");
-    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create presets directory: {}", e))?;
-    Ok(dir.join("presets.json"))
+#[tauri::command]
+async fn rescan_library() -> Result<library::RescanReport, String> {
+    library::rescan_library().await
 }
 
-async fn load_all_presets() -> Result<Vec<Preset>, String> {
-    let path = presets_db_path().await?;
-    if !path.exists() {
-        return Ok(vec![]);
-    }
-    let content =
-        fs::read_to_string(&path).map_err(|e| format!("Failed to read presets DB: {}", e))?;
-    serde_json::from_str(&content).map_err(|e| format!("Failed to parse presets DB: {}", e))
+#[tauri::command]
+async fn list_unmanaged_zzmi_mods() -> Result<Vec<library::UnmanagedZzmiMod>, String> {
+    library::list_unmanaged_zzmi_mods().await
+}
+
+#[tauri::command]
+async fn import_existing_zzmi_mods(
+    imports: Vec<library::ZzmiImportRequest>,
+) -> Result<library::ZzmiImportReport, String> {
+    library::import_existing_zzmi_mods(imports).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn get_link_conflict(modId: String) -> Result<Option<links::LinkConflict>, String> {
+    library::get_link_conflict(&modId).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn resolve_link_conflict(
+    modId: String,
+    resolution: links::ConflictResolution,
+) -> Result<library::ConflictOutcome, String> {
+    library::resolve_link_conflict(&modId, resolution).await
+}
+
+#[tauri::command]
+async fn migrate_link_names() -> Result<library::LinkMigrationReport, String> {
+    library::migrate_link_names().await
+}
+
+#[tauri::command]
+async fn reconcile_active_state(repair: bool) -> Result<reconcile::ReconcileReport, String> {
+    reconcile::reconcile_active_state(repair).await
+}
+
+#[tauri::command]
+async fn detect_conflicts(
+    preset_id: Option<String>,
+) -> Result<Vec<conflicts::HashConflict>, String> {
+    conflicts::detect_conflicts(preset_id.as_deref()).await
+}
+
+#[tauri::command]
+async fn list_key_bindings(
+    preset_id: Option<String>,
+) -> Result<keybindings::KeyBindingReport, String> {
+    keybindings::list_key_bindings(preset_id.as_deref()).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn remap_key_bindings(
+    modId: String,
+    remaps: Vec<keybindings::KeyRemap>,
+) -> Result<manifest::ModManifest, String> {
+    keybindings::remap_key_bindings(&modId, remaps).await
+}
+
+// Undo every ini edit made through Aether, restoring the files as installed
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn revert_mod_edits(modId: String) -> Result<Vec<String>, String> {
+    let m = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == modId)
+        .ok_or("Mod not found")?;
+    let restored = originals::restore_originals(&m)?;
+    activation::refresh_deployment(&m).await?;
+    Ok(restored)
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn lint_mod(modId: String) -> Result<lint::LintReport, String> {
+    lint::lint_mod(&modId).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn suggest_character(modId: String) -> Result<classify::CharacterSuggestion, String> {
+    classify::suggest_character(&modId).await
+}
+
+// Which mods and sections a hash mapping file would change, without
+// writing anything
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn preview_hash_fix(
+    mappingPath: String,
+    modIds: Option<Vec<String>>,
+) -> Result<hashfix::HashFixReport, String> {
+    hashfix::fix_hashes(&mappingPath, modIds, false).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn apply_hash_fix(
+    mappingPath: String,
+    modIds: Option<Vec<String>>,
+) -> Result<hashfix::HashFixReport, String> {
+    hashfix::fix_hashes(&mappingPath, modIds, true).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn get_mod_variables(modId: String) -> Result<Vec<variables::VariableState>, String> {
+    variables::get_mod_variables(&modId).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn set_mod_variables(
+    modId: String,
+    values: Vec<variables::VariableValue>,
+) -> Result<Vec<variables::VariableState>, String> {
+    variables::set_mod_variables(&modId, values).await
+}
+
+// Combine several mods for one character into a new mod cycled by `key`
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn create_merged_mod(
+    title: String,
+    modIds: Vec<String>,
+    key: Option<String>,
+    back: Option<String>,
+) -> Result<lint::AddedMod, String> {
+    merge::create_merged_mod(title, modIds, key, back).await
 }
 
-async fn save_all_presets(presets: &[Preset]) -> Result<(), String> {
-    let path = presets_db_path().await?;
-    let content = serde_json::to_string_pretty(presets)
-        .map_err(|e| format!("Failed to serialize presets: {}", e))?;
-    fs::write(&path, content).map_err(|e| format!("Failed to write presets DB: {}", e))
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn get_merged_mod_info(modId: String) -> Result<merge::MergedModInfo, String> {
+    merge::get_merged_mod_info(&modId).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn regenerate_merged_mod(modId: String) -> Result<Mod, String> {
+    merge::regenerate_merged_mod(&modId).await
+}
+
+// Replace the list of mods and ZZMI files a mod needs
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn set_mod_dependencies(
+    modId: String,
+    dependencies: Vec<dependencies::Dependency>,
+) -> Result<Mod, String> {
+    dependencies::set_mod_dependencies(&modId, dependencies).await
+}
+
+// Place an active mod before or after others; None gives up its place
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn set_load_order(modId: String, loadOrder: Option<u32>) -> Result<Mod, String> {
+    load_order::set_load_order(&modId, loadOrder).await
+}
+
+// Put a mod in a custom slot instead of its character's; None clears it
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn set_mod_group(modId: String, group: Option<String>) -> Result<Mod, String> {
+    exclusive::set_mod_group(&modId, group).await
+}
+
+// Characters or groups that hold one active mod at a time
+#[tauri::command]
+async fn set_exclusive_slots(slots: Vec<String>) -> Result<Vec<String>, String> {
+    exclusive::set_exclusive_slots(slots).await
+}
+
+// Deploy one variant of a multi-variant pack, or all of it with None
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn select_mod_variant(modId: String, variant: Option<String>) -> Result<Mod, String> {
+    variants::select_mod_variant(&modId, variant).await
+}
+
+// Variables that switch between the variants of a merged mod
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn detect_swap_variables(modId: String) -> Result<Vec<unmerge::SwapVariable>, String> {
+    unmerge::detect_swap_variables(&modId).await
+}
+
+// Split a merged mod into one new mod per variant; the original is kept
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn unmerge_mod(
+    modId: String,
+    variable: Option<String>,
+) -> Result<Vec<lint::AddedMod>, String> {
+    unmerge::unmerge_mod(&modId, variable).await
+}
+
+#[tauri::command]
+#[allow(non_snake_case)]
+async fn get_mod_manifest(modId: String) -> Result<manifest::ModManifest, String> {
+    manifest::get_mod_manifest(&modId).await
+}
+
+#[tauri::command]
+async fn set_activation_mode(
+    mode: activation::ActivationMode,
+) -> Result<activation::ModeMigrationReport, String> {
+    activation::set_activation_mode(mode).await
+}
+
+// ===== Library backup commands =====
+#[tauri::command]
+async fn list_library_backups() -> Result<Vec<storage::LibraryBackup>, String> {
+    storage::list_backups().await
+}
+
+#[tauri::command]
+async fn create_library_backup() -> Result<storage::LibraryBackup, String> {
+    storage::create_backup().await
+}
+
+#[tauri::command]
+async fn restore_library_backup(name: String) -> Result<(), String> {
+    storage::restore_backup(&name).await
 }
 
 // ===== Presets commands =====
@@ -530,17 +942,37 @@ async fn list_presets() -> Result<Vec<Preset>, String> {
 }
 
 #[tauri::command]
-async fn create_preset(name: String, mod_ids: Option<Vec<String>>) -> Result<Preset, String> {
+async fn create_preset(
+    name: String,
+    mod_ids: Option<Vec<String>>,
+    capture_variables: Option<bool>,
+) -> Result<Preset, String> {
+    let mods = load_all_mods().await?;
     let final_mod_ids = if let Some(ids) = mod_ids {
         ids
     } else {
         // Fallback to active mods if no ids are provided
-        let mods = load_all_mods().await?;
-        mods.into_iter()
+        mods.iter()
             .filter(|m| m.is_active)
-            .map(|m| m.id)
+            .map(|m| m.id.clone())
             .collect()
     };
+    exclusive::check_exclusive(&load_settings().await?, &mods, &final_mod_ids)?;
+    let load_order = mods
+        .iter()
+        .filter(|m| final_mod_ids.contains(&m.id))
+        .filter_map(|m| Some((m.id.clone(), m.load_order?)))
+        .collect();
+
+    let variables = if capture_variables.unwrap_or(false) {
+        let mods: Vec<Mod> = mods
+            .into_iter()
+            .filter(|m| final_mod_ids.contains(&m.id))
+            .collect();
+        variables::capture_variables(&mods).await?
+    } else {
+        Vec::new()
+    };
 
     let now = Utc::now().to_rfc3339();
     let preset = Preset {
@@ -549,23 +981,20 @@ async fn create_preset(name: String, mod_ids: Option<Vec<String>>) -> Result<Pre
         created_at: now.clone(),
         updated_at: now,
         mod_ids: final_mod_ids,
+        variables,
+        load_order,
     };
 
-    let mut all = load_all_presets().await.unwrap_or_default();
-    all.push(preset.clone());
-    save_all_presets(&all).await?;
+    storage::save_preset(&preset).await?;
     Ok(preset)
 }
 
 #[tauri::command]
 async fn delete_preset(preset_id: String) -> Result<(), String> {
-    let mut all = load_all_presets().await.unwrap_or_default();
-    let len_before = all.len();
-    all.retain(|p| p.id != preset_id);
-    if all.len() == len_before {
+    if !storage::remove_preset(&preset_id).await? {
         return Err("Preset not found".into());
     }
-    save_all_presets(&all).await
+    Ok(())
 }
 
 #[tauri::command]
@@ -574,56 +1003,65 @@ async fn update_preset(
     name: String,
     mod_ids: Vec<String>,
 ) -> Result<(), String> {
+    let mods = load_all_mods().await?;
+    exclusive::check_exclusive(&load_settings().await?, &mods, &mod_ids)?;
     let mut all = load_all_presets().await?;
     if let Some(preset) = all.iter_mut().find(|p| p.id == preset_id) {
         preset.name = name;
+        preset.load_order = mods
+            .iter()
+            .filter(|m| mod_ids.contains(&m.id))
+            .filter_map(|m| Some((m.id.clone(), m.load_order?)))
+            .collect();
         preset.mod_ids = mod_ids;
         preset.updated_at = Utc::now().to_rfc3339();
-        save_all_presets(&all).await
+        storage::save_preset(preset).await
     } else {
         Err("Preset not found".to_string())
     }
 }
 
+// Replace the preset's captured toggle states with the current ones
 #[tauri::command]
-async fn apply_preset(preset_id: String) -> Result<(), String> {
-    let presets = load_all_presets().await?;
-    let preset = presets
+async fn capture_preset_variables(preset_id: String) -> Result<Preset, String> {
+    let mut preset = presets::find_preset(&preset_id).await?;
+    let mods: Vec<Mod> = load_all_mods()
+        .await?
         .into_iter()
-        .find(|p| p.id == preset_id)
-        .ok_or("Preset not found")?;
-
-    let mut mods = load_all_mods().await?;
-    let desired: HashSet<String> = preset.mod_ids.iter().cloned().collect();
-
-    let settings = load_settings().await?;
-    let zzmi_path = settings
-        .zzmi_mods_path
-        .ok_or("ZZMI mods path not configured. Please set it in settings.")?;
-    fs::create_dir_all(&zzmi_path)
-        .map_err(|e| format!("Failed to create ZZMI mods directory: {}", e))?;
+        .filter(|m| preset.mod_ids.contains(&m.id))
+        .collect();
+    preset.variables = variables::capture_variables(&mods).await?;
+    preset.updated_at = Utc::now().to_rfc3339();
+    storage::save_preset(&preset).await?;
+    Ok(preset)
+}
 
-    for m in mods.iter_mut() {
-        let should_be_active = desired.contains(&m.id);
-        let zzmi_file_path = format!("{}/{}", zzmi_path, m.original_name);
-
-        if should_be_active && !m.is_active {
-            create_symlink(Path::new(&m.file_path), Path::new(&zzmi_file_path))
-                .map_err(|e| format!("Failed to create mod symlink to ZZMI: {}", e))?;
-            m.is_active = true;
-        } else if !should_be_active && m.is_active {
-            remove_symlink(Path::new(&zzmi_file_path))
-                .map_err(|e| format!("Failed to remove mod symlink from ZZMI: {}", e))?;
-            m.is_active = false;
-        }
-    }
+#[tauri::command]
+async fn plan_preset(preset_id: String) -> Result<presets::PresetPlan, String> {
+    presets::plan_preset(&preset_id).await
+}
 
-    save_all_mods(&mods).await
+#[tauri::command]
+async fn apply_preset(preset_id: String) -> Result<presets::PresetPlan, String> {
+    presets::apply_preset(&preset_id).await
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        .setup(|_app| {
+            tauri::async_runtime::spawn(async {
+                // Move links created before namespaced link names to their new names
+                if let Err(e) = library::migrate_link_names().await {
+                    println!("Failed to migrate mod link names: {}", e);
+                }
+                // Then make the recorded active state match the ZZMI folder
+                if let Err(e) = reconcile::reconcile_active_state(true).await {
+                    println!("Failed to reconcile active mods: {}", e);
+                }
+            });
+            Ok(())
+        })
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
@@ -637,15 +1075,50 @@ pub fn run() {
             update_mod,
             delete_mod,
             get_mod_stats,
+            rescan_library,
+            list_unmanaged_zzmi_mods,
+            import_existing_zzmi_mods,
+            get_link_conflict,
+            resolve_link_conflict,
+            migrate_link_names,
+            reconcile_active_state,
+            get_mod_manifest,
+            detect_conflicts,
+            list_key_bindings,
+            remap_key_bindings,
+            revert_mod_edits,
+            lint_mod,
+            suggest_character,
+            preview_hash_fix,
+            apply_hash_fix,
+            get_mod_variables,
+            set_mod_variables,
+            create_merged_mod,
+            get_merged_mod_info,
+            regenerate_merged_mod,
+            detect_swap_variables,
+            unmerge_mod,
+            select_mod_variant,
+            set_mod_group,
+            set_exclusive_slots,
+            set_mod_dependencies,
+            set_load_order,
             get_settings,
             update_settings,
+            set_activation_mode,
             select_folder,
             select_mod_folder,
+            select_mod_archive,
             list_presets,
             create_preset,
             delete_preset,
             update_preset,
-            apply_preset
+            capture_preset_variables,
+            plan_preset,
+            apply_preset,
+            list_library_backups,
+            create_library_backup,
+            restore_library_backup
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
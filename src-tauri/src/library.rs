@@ -0,0 +1,493 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use uuid::Uuid;
+
+use crate::activation::{activator_for, Activator};
+use crate::classify;
+use crate::links::{
+    build_link_name, create_symlink, inspect_link_path, is_managed_entry, link_path_for,
+    link_points_to, remove_symlink, ConflictResolution, LinkConflict,
+};
+use crate::lint::{lint_added, AddedMod};
+use crate::storage::{load_all_mods, load_settings, save_all_mods, save_mod_metadata};
+use crate::variants::{deployed_path, find_variants};
+use crate::{get_app_config_dir, move_dir, storage_folder_for, validate_mod_structure, Mod};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RescanReport {
+    pub scanned_folders: usize,
+    // Folders in the library that had no entry and were added back
+    pub added: Vec<Mod>,
+    // Entries whose folder is gone; they are kept but flagged `is_missing`
+    pub missing: Vec<Mod>,
+    // Previously missing entries whose folder is back
+    pub recovered: Vec<Mod>,
+}
+
+// Paths are stored as strings built with `/`; compare them component-wise so
+// separator differences don't make a known folder look new
+fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+fn subdirectories(dir: &Path) -> Result<Vec<(String, PathBuf)>, String> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))? {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_hidden(&name) && entry.path().is_dir() {
+            dirs.push((name, entry.path()));
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+// Every mod folder in the managed library, with the character inferred from
+// the folder it lives in
+fn library_folders() -> Result<Vec<(Option<String>, String, String)>, String> {
+    let app_data_path = get_app_config_dir()?
+        .join("mods")
+        .to_string_lossy()
+        .to_string();
+    let mut folders = Vec::new();
+
+    let characters_dir = format!("{}/characters", app_data_path);
+    for (character, char_path) in subdirectories(Path::new(&characters_dir))? {
+        for (name, _) in subdirectories(&char_path)? {
+            let file_path = format!("{}/{}/{}", characters_dir, character, name);
+            folders.push((Some(character.clone()), name, file_path));
+        }
+    }
+
+    let other_dir = format!("{}/othermods", app_data_path);
+    for (name, _) in subdirectories(Path::new(&other_dir))? {
+        let file_path = format!("{}/{}", other_dir, name);
+        folders.push((None, name, file_path));
+    }
+
+    Ok(folders)
+}
+
+fn folder_date(path: &Path) -> String {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map(DateTime::<Utc>::from)
+        .unwrap_or_else(|_| Utc::now())
+        .to_rfc3339()
+}
+
+pub async fn rescan_library() -> Result<RescanReport, String> {
+    let mut mods = load_all_mods().await?;
+    let known: HashSet<PathBuf> = mods
+        .iter()
+        .map(|m| normalize(Path::new(&m.file_path)))
+        .collect();
+
+    let folders = library_folders()?;
+    let mut report = RescanReport {
+        scanned_folders: folders.len(),
+        added: Vec::new(),
+        missing: Vec::new(),
+        recovered: Vec::new(),
+    };
+
+    for (character, name, file_path) in folders {
+        if known.contains(&normalize(Path::new(&file_path))) {
+            continue;
+        }
+        println!("Rescan: adding unknown library folder {}", file_path);
+        let mut added = Mod {
+            id: Uuid::new_v4().to_string(),
+            title: name.clone(),
+            description: None,
+            thumbnail: None,
+            is_active: false,
+            date_added: folder_date(Path::new(&file_path)),
+            character,
+            variants: find_variants(Path::new(&file_path)),
+            file_path,
+            original_name: name,
+            is_missing: false,
+            link_name: String::new(),
+            selected_variant: None,
+            group: None,
+            dependencies: Vec::new(),
+            load_order: None,
+        };
+        added.link_name = build_link_name(&added);
+        report.added.push(added);
+    }
+
+    for m in mods.iter_mut() {
+        let exists = Path::new(&m.file_path).exists();
+        if !exists && !m.is_missing {
+            println!("Rescan: mod folder missing for {} ({})", m.title, m.file_path);
+            m.is_missing = true;
+            report.missing.push(m.clone());
+        } else if exists && m.is_missing {
+            println!("Rescan: mod folder found again for {} ({})", m.title, m.file_path);
+            m.is_missing = false;
+            report.recovered.push(m.clone());
+        }
+    }
+
+    let changed: Vec<Mod> = report
+        .added
+        .iter()
+        .chain(report.missing.iter())
+        .chain(report.recovered.iter())
+        .cloned()
+        .collect();
+    save_all_mods(&changed).await?;
+
+    Ok(report)
+}
+
+// ===== Adopting existing ZZMI mods =====
+// XXMI-style launchers disable a mod by prefixing its folder name
+const DISABLED_PREFIXES: [&str; 2] = ["DISABLED_", "DISABLED "];
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnmanagedZzmiMod {
+    pub folder_name: String,
+    pub path: String,
+    // Folder name without any DISABLED prefix
+    pub mod_name: String,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZzmiImportRequest {
+    pub folder_name: String,
+    pub character: Option<String>,
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZzmiImportFailure {
+    pub folder_name: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ZzmiImportReport {
+    pub imported: Vec<AddedMod>,
+    pub failed: Vec<ZzmiImportFailure>,
+}
+
+fn split_disabled_prefix(folder_name: &str) -> (String, bool) {
+    for prefix in DISABLED_PREFIXES {
+        let matches = folder_name
+            .get(..prefix.len())
+            .map(|head| head.eq_ignore_ascii_case(prefix))
+            .unwrap_or(false);
+        if matches && folder_name.len() > prefix.len() {
+            return (folder_name[prefix.len()..].to_string(), false);
+        }
+    }
+    (folder_name.to_string(), true)
+}
+
+pub fn unique_destination(storage_folder: &str, name: &str) -> String {
+    let mut destination = format!("{}/{}", storage_folder, name);
+    let mut counter = 2;
+    while Path::new(&destination).exists() {
+        destination = format!("{}/{} ({})", storage_folder, name, counter);
+        counter += 1;
+    }
+    destination
+}
+
+async fn zzmi_mods_dir() -> Result<String, String> {
+    load_settings()
+        .await?
+        .zzmi_mods_path
+        .ok_or_else(|| "ZZMI mods path not configured. Please set it in settings.".to_string())
+}
+
+pub async fn list_unmanaged_zzmi_mods() -> Result<Vec<UnmanagedZzmiMod>, String> {
+    let zzmi_path = zzmi_mods_dir().await?;
+
+    let mut found = Vec::new();
+    for (folder_name, path) in subdirectories(Path::new(&zzmi_path))? {
+        if is_managed_entry(&path) {
+            continue;
+        }
+        let (mod_name, is_active) = split_disabled_prefix(&folder_name);
+        found.push(UnmanagedZzmiMod {
+            folder_name,
+            path: path.to_string_lossy().to_string(),
+            mod_name,
+            is_active,
+        });
+    }
+    Ok(found)
+}
+
+// Move one ZZMI folder into the library and, if it was enabled, put a managed
+// link back in its place so the game keeps loading it
+fn adopt_zzmi_folder(
+    zzmi_path: &str,
+    request: &ZzmiImportRequest,
+    activator: &dyn Activator,
+) -> Result<Mod, String> {
+    // Only a folder directly inside the ZZMI mods folder may be taken
+    let mut components = Path::new(&request.folder_name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) {
+        return Err(format!(
+            "{} is not a folder in the ZZMI mods folder",
+            request.folder_name
+        ));
+    }
+
+    let source = Path::new(zzmi_path).join(&request.folder_name);
+    if !source.is_dir() || is_managed_entry(&source) {
+        return Err("Folder is not an unmanaged mod in the ZZMI mods folder".to_string());
+    }
+    validate_mod_structure(&source)?;
+
+    let (mod_name, is_active) = split_disabled_prefix(&request.folder_name);
+    let title = request.title.clone().unwrap_or_else(|| mod_name.clone());
+    let character = match &request.character {
+        Some(c) => Some(c.clone()),
+        None => classify::detect_character(&source, &[&title, &mod_name]),
+    };
+    let storage_folder = storage_folder_for(character.as_deref())?;
+    let destination = unique_destination(&storage_folder, &mod_name);
+
+    println!("Adopting {} into {}", source.display(), destination);
+    move_dir(&source, Path::new(&destination))
+        .map_err(|e| format!("Failed to move mod into library: {}", e))?;
+
+    let mut adopted = Mod {
+        id: Uuid::new_v4().to_string(),
+        title,
+        description: None,
+        thumbnail: None,
+        is_active,
+        date_added: Utc::now().to_rfc3339(),
+        character,
+        file_path: destination.clone(),
+        original_name: mod_name,
+        is_missing: false,
+        link_name: String::new(),
+        // It was loading every variant already; that stays so until one is picked
+        variants: find_variants(Path::new(&destination)),
+        selected_variant: None,
+        group: None,
+        dependencies: Vec::new(),
+        load_order: None,
+    };
+    adopted.link_name = build_link_name(&adopted);
+
+    if is_active {
+        if let Err(e) = activator.activate(zzmi_path, &adopted) {
+            // Put the folder back so the game setup is unchanged
+            let _ = move_dir(Path::new(&destination), &source);
+            return Err(format!("Failed to add mod to ZZMI: {}", e));
+        }
+    }
+
+    Ok(adopted)
+}
+
+pub async fn import_existing_zzmi_mods(
+    requests: Vec<ZzmiImportRequest>,
+) -> Result<ZzmiImportReport, String> {
+    let zzmi_path = zzmi_mods_dir().await?;
+    let activator = activator_for(load_settings().await?.activation_mode);
+    let mut report = ZzmiImportReport {
+        imported: Vec::new(),
+        failed: Vec::new(),
+    };
+
+    for request in &requests {
+        match adopt_zzmi_folder(&zzmi_path, request, activator.as_ref()) {
+            Ok(m) => {
+                // Record each adoption right away; the folder has already moved
+                save_mod_metadata(&m).await?;
+                report.imported.push(lint_added(m));
+            }
+            Err(error) => {
+                println!("Failed to import {}: {}", request.folder_name, error);
+                report.failed.push(ZzmiImportFailure {
+                    folder_name: request.folder_name.clone(),
+                    error,
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+// ===== Link conflicts =====
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictOutcome {
+    // The mod whose link was blocked, after the resolution was applied
+    pub updated_mod: Mod,
+    // The user's folder, when it was adopted into the library
+    pub adopted: Option<AddedMod>,
+    // New name of the user's entry, when it was renamed out of the way
+    pub renamed_to: Option<String>,
+}
+
+async fn find_mod(mod_id: &str) -> Result<Mod, String> {
+    load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or_else(|| "Mod not found".to_string())
+}
+
+pub async fn get_link_conflict(mod_id: &str) -> Result<Option<LinkConflict>, String> {
+    let m = find_mod(mod_id).await?;
+    let zzmi_path = zzmi_mods_dir().await?;
+    Ok(inspect_link_path(Path::new(&link_path_for(&zzmi_path, &m))))
+}
+
+pub async fn resolve_link_conflict(
+    mod_id: &str,
+    resolution: ConflictResolution,
+) -> Result<ConflictOutcome, String> {
+    let mut m = find_mod(mod_id).await?;
+    let zzmi_path = zzmi_mods_dir().await?;
+    let activator = activator_for(load_settings().await?.activation_mode);
+    let link_path = PathBuf::from(link_path_for(&zzmi_path, &m));
+    let link_folder_name = link_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let conflict = inspect_link_path(&link_path).ok_or("There is no conflict to resolve")?;
+
+    let mut outcome = ConflictOutcome {
+        updated_mod: m.clone(),
+        adopted: None,
+        renamed_to: None,
+    };
+
+    match resolution {
+        ConflictResolution::Skip => {}
+        ConflictResolution::Rename => {
+            let mut counter = 1;
+            let renamed = loop {
+                let suffix = if counter == 1 {
+                    " (unmanaged)".to_string()
+                } else {
+                    format!(" (unmanaged {})", counter)
+                };
+                let candidate =
+                    Path::new(&zzmi_path).join(format!("{}{}", link_folder_name, suffix));
+                if fs::symlink_metadata(&candidate).is_err() {
+                    break candidate;
+                }
+                counter += 1;
+            };
+
+            println!("Renaming {} to {}", conflict.link_path, renamed.display());
+            fs::rename(&link_path, &renamed)
+                .map_err(|e| format!("Failed to rename existing folder: {}", e))?;
+            if let Err(e) = activator.activate(&zzmi_path, &m) {
+                // Put the user's folder back where it was
+                if let Err(restore) = fs::rename(&renamed, &link_path) {
+                    println!("Failed to rename {} back: {}", renamed.display(), restore);
+                }
+                return Err(format!("Failed to add mod to ZZMI: {}", e));
+            }
+
+            m.is_active = true;
+            save_mod_metadata(&m).await?;
+            outcome.updated_mod = m;
+            outcome.renamed_to = Some(renamed.to_string_lossy().to_string());
+        }
+        ConflictResolution::Adopt => {
+            if !link_path.is_dir() || fs::read_link(&link_path).is_ok() {
+                return Err("Only a real folder can be adopted into the library".to_string());
+            }
+            let adopted = adopt_zzmi_folder(
+                &zzmi_path,
+                &ZzmiImportRequest {
+                    folder_name: link_folder_name,
+                    character: m.character.clone(),
+                    title: None,
+                },
+                activator.as_ref(),
+            )?;
+            save_mod_metadata(&adopted).await?;
+            outcome.adopted = Some(lint_added(adopted));
+        }
+    }
+
+    Ok(outcome)
+}
+
+// ===== Link names =====
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkMigrationReport {
+    // Mods that received a namespaced link name
+    pub renamed: Vec<Mod>,
+    // Active mods whose old link could not be moved; they were deactivated
+    pub deactivated: Vec<Mod>,
+}
+
+// Give every mod without one a namespaced link name, moving the links of
+// active mods from `{zzmi}/{original_name}` to their new location
+pub async fn migrate_link_names() -> Result<LinkMigrationReport, String> {
+    let mut mods = load_all_mods().await?;
+    let zzmi_path = load_settings().await?.zzmi_mods_path;
+    let mut report = LinkMigrationReport {
+        renamed: Vec::new(),
+        deactivated: Vec::new(),
+    };
+
+    for m in mods.iter_mut().filter(|m| m.link_name.is_empty()) {
+        let old_link = zzmi_path.as_deref().map(|z| link_path_for(z, m));
+        m.link_name = build_link_name(m);
+
+        if let (true, Some(zzmi), Some(old_link)) = (m.is_active, zzmi_path.as_deref(), old_link) {
+            let old_link = Path::new(&old_link);
+            let new_link = link_path_for(zzmi, m);
+
+            // Only the link that really belongs to this mod may be moved; a
+            // same-named link may belong to a mod that replaced it
+            let moved = link_points_to(old_link, m)
+                && remove_symlink(old_link).unwrap_or(false)
+                && create_symlink(&deployed_path(m), Path::new(&new_link)).is_ok();
+
+            if moved {
+                println!("Moved link {} to {}", old_link.display(), new_link);
+            } else {
+                println!("Could not move link for {}; marking it inactive", m.title);
+                m.is_active = false;
+                report.deactivated.push(m.clone());
+            }
+        }
+
+        report.renamed.push(m.clone());
+    }
+
+    save_all_mods(&report.renamed).await?;
+    Ok(report)
+}
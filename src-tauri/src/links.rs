@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::variants::deployed_path;
+use crate::{get_app_config_dir, Mod};
+
+// What is sitting at a link path that Aether does not own
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkConflictKind {
+    Directory,
+    File,
+    ForeignLink,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkConflict {
+    pub link_path: String,
+    pub kind: LinkConflictKind,
+    // Where a foreign link points, if it is one
+    pub link_target: Option<String>,
+}
+
+// How the UI asked us to settle a conflict
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    // Move the user's own entry aside and activate the mod
+    Rename,
+    // Take the user's folder into the library as a mod of its own
+    Adopt,
+    // Leave everything as it is
+    Skip,
+}
+
+#[derive(Debug)]
+pub enum LinkError {
+    Conflict(LinkConflict),
+    Io(io::Error),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::Conflict(conflict) => write!(
+                f,
+                "{} already exists and is not managed by Aether Manager (rename, adopt or skip it)",
+                conflict.link_path
+            ),
+            LinkError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<io::Error> for LinkError {
+    fn from(e: io::Error) -> Self {
+        LinkError::Io(e)
+    }
+}
+
+// File dropped into every folder the copy-based activation modes deploy; it
+// holds the id of the mod the folder belongs to
+pub const MANAGED_MARKER: &str = ".aether-managed";
+
+// Longest sanitized title kept in a link name
+const MAX_LINK_TITLE_LEN: usize = 40;
+
+pub fn sanitize_link_part(value: &str) -> String {
+    let mut out = String::new();
+    for c in value.trim().chars() {
+        if c.is_alphanumeric() || c == '-' {
+            out.push(c);
+        } else if !out.ends_with('_') {
+            out.push('_');
+        }
+    }
+    let trimmed: String = out
+        .trim_matches('_')
+        .chars()
+        .take(MAX_LINK_TITLE_LEN)
+        .collect();
+    trimmed.trim_end_matches('_').to_string()
+}
+
+// Collision-free folder name for a mod's link in the ZZMI mods folder:
+// character + sanitized title + short id, e.g. `ellen_Maid_Outfit_1a2b3c4d`
+pub fn build_link_name(m: &Mod) -> String {
+    let character = m
+        .character
+        .as_deref()
+        .map(sanitize_link_part)
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| "other".to_string());
+    let title = match sanitize_link_part(&m.title) {
+        t if t.is_empty() => sanitize_link_part(&m.original_name),
+        t => t,
+    };
+    let short_id: String = m.id.chars().filter(|c| *c != '-').take(8).collect();
+
+    if title.is_empty() {
+        format!("{}_{}", character, short_id)
+    } else {
+        format!("{}_{}_{}", character, title, short_id)
+    }
+}
+
+// Folder name a mod appears under in the ZZMI mods folder. Mods created
+// before link names existed are still linked under their original folder name.
+// A load order goes in front, zero-padded so that 3DMigoto, which loads the
+// folders in name order, loads them in that order and ahead of unnumbered ones.
+pub fn link_folder_name(m: &Mod) -> String {
+    let name = if m.link_name.is_empty() {
+        &m.original_name
+    } else {
+        &m.link_name
+    };
+    match m.load_order {
+        Some(order) => format!("{:04}_{}", order, name),
+        None => name.clone(),
+    }
+}
+
+// Where a mod's link lives in the ZZMI mods folder
+pub fn link_path_for(zzmi_path: &str, m: &Mod) -> String {
+    format!("{}/{}", zzmi_path, link_folder_name(m))
+}
+
+// Whether the link at `path` points at this mod's library folder (or the
+// selected variant in it)
+pub fn link_points_to(path: &Path, m: &Mod) -> bool {
+    let Ok(target) = fs::read_link(path) else {
+        return false;
+    };
+    let target = target.canonicalize().unwrap_or(target);
+    let folder = deployed_path(m);
+    let folder = folder.canonicalize().unwrap_or(folder);
+    target == folder
+}
+
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path)
+        .map(|m| m.file_type().is_symlink())
+        .unwrap_or(false)
+}
+
+// Whether `path` is a link that points into the app-managed library
+pub fn is_library_link(path: &Path) -> bool {
+    if !is_symlink(path) {
+        return false;
+    }
+
+    let (Ok(target), Ok(library)) = (fs::read_link(path), get_app_config_dir()) else {
+        return false;
+    };
+    let library = library.join("mods");
+    let library = library.canonicalize().unwrap_or(library);
+    let target = target.canonicalize().unwrap_or(target);
+    target.starts_with(&library)
+}
+
+// Id of the mod a deployed copy at `path` belongs to, if it is one of ours
+pub fn managed_copy_owner(path: &Path) -> Option<String> {
+    if is_symlink(path) {
+        return None;
+    }
+    fs::read_to_string(path.join(MANAGED_MARKER))
+        .ok()
+        .map(|id| id.trim().to_string())
+}
+
+// Whether `path` is a link or a deployed copy that Aether put there
+pub fn is_managed_entry(path: &Path) -> bool {
+    is_library_link(path) || managed_copy_owner(path).is_some()
+}
+
+// Describe whatever occupies `path` if it isn't ours to replace. Free paths
+// and our own links and copies report no conflict.
+pub fn inspect_link_path(path: &Path) -> Option<LinkConflict> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    if is_managed_entry(path) {
+        return None;
+    }
+
+    let (kind, link_target) = if metadata.file_type().is_symlink() {
+        let target = fs::read_link(path)
+            .ok()
+            .map(|t| t.to_string_lossy().to_string());
+        (LinkConflictKind::ForeignLink, target)
+    } else if metadata.is_dir() {
+        (LinkConflictKind::Directory, None)
+    } else {
+        (LinkConflictKind::File, None)
+    };
+
+    Some(LinkConflict {
+        link_path: path.to_string_lossy().to_string(),
+        kind,
+        link_target,
+    })
+}
+
+// Delete the link itself, never what it points to
+fn remove_link_entry(path: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        // Directory symlinks and junctions are directories to Windows
+        fs::remove_dir(path).or_else(|_| fs::remove_file(path))
+    }
+
+    #[cfg(not(windows))]
+    {
+        fs::remove_file(path)
+    }
+}
+
+// Clear `dst` for a new deployment. Only a link or copy of ours may be
+// replaced; anything else is reported as a conflict.
+pub fn clear_link_path(dst: &Path) -> Result<(), LinkError> {
+    if let Some(conflict) = inspect_link_path(dst) {
+        return Err(LinkError::Conflict(conflict));
+    }
+    if is_symlink(dst) {
+        remove_link_entry(dst)?;
+    } else if dst.is_dir() {
+        fs::remove_dir_all(dst)?;
+    }
+    Ok(())
+}
+
+// Create symlink (cross-platform). Only an existing link of ours may be
+// replaced; anything else at `dst` is reported as a conflict.
+pub fn create_symlink(src: &Path, dst: &Path) -> Result<(), LinkError> {
+    clear_link_path(dst)?;
+
+    // Create parent directory
+    if let Some(parent) = dst.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    #[cfg(windows)]
+    {
+        if src.is_dir() {
+            std::os::windows::fs::symlink_dir(src, dst)?;
+        } else {
+            std::os::windows::fs::symlink_file(src, dst)?;
+        }
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(src, dst)?;
+    }
+
+    Ok(())
+}
+
+// Remove symlink safely. Returns false when there was nothing of ours to
+// remove; unmanaged content at `path` is left untouched.
+pub fn remove_symlink(path: &Path) -> io::Result<bool> {
+    if !is_library_link(path) {
+        if fs::symlink_metadata(path).is_ok() {
+            println!(
+                "Leaving {} in place: it is not managed by Aether Manager",
+                path.display()
+            );
+        }
+        return Ok(false);
+    }
+
+    remove_link_entry(path)?;
+    Ok(true)
+}
@@ -0,0 +1,414 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::ini::{parse_ini, read_ini};
+use crate::links::MANAGED_MARKER;
+use crate::manifest::{mod_ini_files, relative_path, resolve_resource_path};
+use crate::storage::load_all_mods;
+use crate::variants::deployed_path;
+use crate::Mod;
+
+// Files at least this big that no ini refers to are worth a warning; they
+// are usually left-over exports that bloat the mod
+const LARGE_FILE_BYTES: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    // The mod will not load or render correctly
+    Error,
+    // Likely a mistake, but the mod may still work
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    // Stable identifier for the check, e.g. `missing_file`
+    pub code: String,
+    pub message: String,
+    // Relative to the mod folder
+    pub file: Option<String>,
+    pub line: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintReport {
+    pub mod_id: String,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl LintReport {
+    fn push(&mut self, severity: Severity, code: &str, message: String, file: &str, line: usize) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            code: code.to_string(),
+            message,
+            file: Some(file.to_string()),
+            line: Some(line),
+        });
+    }
+}
+
+// Every file in the mod except ini files and the folders XXMI skips,
+// relative path -> size
+fn mod_files(root: &Path, dir: &Path, out: &mut HashMap<String, u64>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_lowercase();
+        if name.starts_with("disabled") || name == MANAGED_MARKER {
+            continue;
+        }
+        if path.is_dir() {
+            mod_files(root, &path, out);
+        } else if !name.ends_with(".ini") {
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            out.insert(relative_path(root, &path), size);
+        }
+    }
+}
+
+// Look `relative` up one component at a time, preferring an exact match and
+// falling back to one that differs in case; returns the path as it is
+// spelled on disk. Windows' own lookups would hide the difference.
+fn find_on_disk(root: &Path, relative: &str) -> Option<String> {
+    let mut current = root.to_path_buf();
+    for part in relative.split('/') {
+        let entries: Vec<fs::DirEntry> = fs::read_dir(&current).ok()?.flatten().collect();
+        let found = entries
+            .iter()
+            .find(|e| e.file_name().to_string_lossy() == part)
+            .or_else(|| {
+                entries
+                    .iter()
+                    .find(|e| e.file_name().to_string_lossy().eq_ignore_ascii_case(part))
+            })?;
+        current = found.path();
+    }
+    Some(relative_path(root, &current))
+}
+
+fn is_absolute_reference(filename: &str) -> bool {
+    let filename = filename.trim().trim_matches('"');
+    filename.starts_with('/')
+        || filename.starts_with('\\')
+        || filename.as_bytes().get(1) == Some(&b':')
+}
+
+pub fn lint(m: &Mod) -> Result<LintReport, String> {
+    let (root, files) = mod_ini_files(m)?;
+    let mut report = LintReport {
+        mod_id: m.id.clone(),
+        diagnostics: Vec::new(),
+    };
+
+    if files.is_empty() {
+        report.diagnostics.push(Diagnostic {
+            severity: Severity::Error,
+            code: "no_ini".to_string(),
+            message: "The mod has no .ini file, so 3DMigoto will not load anything from it"
+                .to_string(),
+            file: None,
+            line: None,
+        });
+    }
+
+    let mut referenced: HashSet<String> = HashSet::new();
+
+    for file in &files {
+        let ini_file = relative_path(&root, file);
+        let ini_dir = ini_file.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+        let text = read_ini(file).map_err(|e| format!("Failed to read {}: {}", ini_file, e))?;
+        let doc = parse_ini(&text);
+
+        for error in &doc.errors {
+            report.push(
+                Severity::Error,
+                "syntax",
+                error.message.clone(),
+                &ini_file,
+                error.line,
+            );
+        }
+
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for section in &doc.sections {
+            let name = section.name.to_lowercase();
+            if let Some(first) = seen.get(&name) {
+                report.push(
+                    Severity::Warning,
+                    "duplicate_section",
+                    format!(
+                        "[{}] is already defined on line {}; 3DMigoto only uses one of them",
+                        section.name, first
+                    ),
+                    &ini_file,
+                    section.line,
+                );
+            } else {
+                seen.insert(name, section.line);
+            }
+
+            if !section.has_prefix("Resource") {
+                continue;
+            }
+            for entry in section
+                .entries
+                .iter()
+                .filter(|e| e.key.eq_ignore_ascii_case("filename"))
+            {
+                if is_absolute_reference(&entry.value) {
+                    report.push(
+                        Severity::Error,
+                        "absolute_path",
+                        format!(
+                            "{} is an absolute path and will break on any other PC",
+                            entry.value
+                        ),
+                        &ini_file,
+                        entry.line,
+                    );
+                    continue;
+                }
+                let Some(path) = resolve_resource_path(ini_dir, &entry.value) else {
+                    report.push(
+                        Severity::Warning,
+                        "outside_mod",
+                        format!("{} points outside the mod folder", entry.value),
+                        &ini_file,
+                        entry.line,
+                    );
+                    continue;
+                };
+
+                match find_on_disk(&root, &path) {
+                    Some(actual) if actual == path => {
+                        referenced.insert(path.to_lowercase());
+                    }
+                    Some(actual) => {
+                        report.push(
+                            Severity::Warning,
+                            "wrong_case",
+                            format!(
+                                "{} is spelled {} on disk; this only works on case-insensitive file systems",
+                                entry.value, actual
+                            ),
+                            &ini_file,
+                            entry.line,
+                        );
+                        referenced.insert(path.to_lowercase());
+                    }
+                    None => report.push(
+                        Severity::Error,
+                        "missing_file",
+                        format!("{} does not exist in the mod folder", entry.value),
+                        &ini_file,
+                        entry.line,
+                    ),
+                }
+            }
+        }
+    }
+
+    if !Path::new(&m.file_path).is_dir() {
+        // Single-file mods share their folder with other mods
+        return Ok(report);
+    }
+
+    let mut sizes = HashMap::new();
+    mod_files(&root, &deployed_path(m), &mut sizes);
+    let mut unreferenced: Vec<(String, u64)> = sizes
+        .into_iter()
+        .filter(|(path, size)| {
+            *size >= LARGE_FILE_BYTES && !referenced.contains(&path.to_lowercase())
+        })
+        .collect();
+    unreferenced.sort();
+    for (path, size) in unreferenced {
+        report.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            code: "unreferenced_file".to_string(),
+            message: format!(
+                "{} ({:.1} MB) is not used by any ini file",
+                path,
+                size as f64 / (1024.0 * 1024.0)
+            ),
+            file: Some(path),
+            line: None,
+        });
+    }
+
+    Ok(report)
+}
+
+// A mod that was just added to the library, with what linting found in it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddedMod {
+    #[serde(rename = "mod")]
+    pub added: Mod,
+    pub lint: LintReport,
+}
+
+// Lint a mod as it enters the library. A lint that can't run ends up in the
+// report instead of failing whatever added the mod.
+pub fn lint_added(m: Mod) -> AddedMod {
+    let lint = lint(&m).unwrap_or_else(|e| LintReport {
+        mod_id: m.id.clone(),
+        diagnostics: vec![Diagnostic {
+            severity: Severity::Warning,
+            code: "lint_failed".to_string(),
+            message: format!("The mod files could not be checked: {}", e),
+            file: None,
+            line: None,
+        }],
+    });
+    AddedMod { added: m, lint }
+}
+
+pub async fn lint_mod(mod_id: &str) -> Result<LintReport, String> {
+    let m = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or("Mod not found")?;
+    lint(&m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_mod(name: &str, files: &[(&str, &[u8])]) -> (PathBuf, Mod) {
+        let dir = std::env::temp_dir().join(format!("aether-lint-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, content) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let m = Mod {
+            id: name.to_string(),
+            title: name.to_string(),
+            description: None,
+            thumbnail: None,
+            is_active: false,
+            date_added: String::new(),
+            character: None,
+            file_path: dir.to_string_lossy().to_string(),
+            original_name: name.to_string(),
+            is_missing: false,
+            link_name: String::new(),
+            variants: Vec::new(),
+            selected_variant: None,
+            group: None,
+            dependencies: Vec::new(),
+            load_order: None,
+        };
+        (dir, m)
+    }
+
+    fn codes(report: &LintReport) -> Vec<(&str, Option<usize>)> {
+        report
+            .diagnostics
+            .iter()
+            .map(|d| (d.code.as_str(), d.line))
+            .collect()
+    }
+
+    #[test]
+    fn checks_resource_filenames() {
+        let ini = "[ResourceA]\nfilename = Textures/a.dds\n[ResourceB]\nfilename = textures/B.dds\n[ResourceC]\nfilename = Textures/missing.dds\n[ResourceD]\nfilename = C:\\Mods\\d.dds\n[ResourceE]\nfilename = ../../e.dds\n[TextureOverrideF]\nfilename = nowhere.dds\n";
+        let (dir, m) = temp_mod(
+            "filenames",
+            &[
+                ("mod.ini", ini.as_bytes()),
+                ("Textures/a.dds", b""),
+                ("Textures/b.dds", b""),
+            ],
+        );
+        let report = lint(&m).unwrap();
+        assert_eq!(
+            codes(&report),
+            [
+                ("wrong_case", Some(4)),
+                ("missing_file", Some(6)),
+                ("absolute_path", Some(8)),
+                ("outside_mod", Some(10)),
+            ]
+        );
+        assert_eq!(report.diagnostics[0].file.as_deref(), Some("mod.ini"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn duplicate_sections_are_still_checked() {
+        let ini = "[ResourceA]\nfilename = a.dds\n[resourcea]\nfilename = missing.dds\n";
+        let (dir, m) = temp_mod("duplicate", &[("mod.ini", ini.as_bytes()), ("a.dds", b"")]);
+        assert_eq!(
+            codes(&lint(&m).unwrap()),
+            [("duplicate_section", Some(3)), ("missing_file", Some(4))]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolves_filenames_next_to_nested_ini_files() {
+        let ini = "[ResourceA]\nfilename = ..\\Shared\\a.dds\n[ResourceB]\nfilename = \"b.dds\"\n";
+        let (dir, m) = temp_mod(
+            "nested",
+            &[
+                ("Body/body.ini", ini.as_bytes()),
+                ("Body/b.dds", b""),
+                ("Shared/a.dds", b""),
+            ],
+        );
+        assert!(lint(&m).unwrap().diagnostics.is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_syntax_errors_and_mods_without_ini() {
+        let (dir, m) = temp_mod("syntax", &[("mod.ini", b"[Open\nbroken line\n")]);
+        assert_eq!(
+            codes(&lint(&m).unwrap()),
+            [("syntax", Some(1)), ("syntax", Some(2))]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (dir, m) = temp_mod("noini", &[("a.dds", b"")]);
+        assert_eq!(codes(&lint(&m).unwrap()), [("no_ini", None)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn warns_about_large_files_no_ini_uses() {
+        let large = vec![0u8; LARGE_FILE_BYTES as usize];
+        let (dir, m) = temp_mod(
+            "unreferenced",
+            &[
+                ("mod.ini", b"[ResourceA]\nfilename = used.dds\n"),
+                ("used.dds", &large),
+                ("Export/unused.blend", &large),
+                ("small.txt", b"notes"),
+                ("DISABLED_old/huge.dds", &large),
+            ],
+        );
+        let report = lint(&m).unwrap();
+        assert_eq!(codes(&report), [("unreferenced_file", None)]);
+        assert_eq!(
+            report.diagnostics[0].file.as_deref(),
+            Some("Export/unused.blend")
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use crate::activation::{activator_for, ActivationMode, Activator};
+use crate::manifest::manifest_for;
+use crate::storage::{load_all_mods, load_settings, save_mod_metadata};
+use crate::variables::{variable_states, write_saved_values};
+use crate::Mod;
+
+// 3DMigoto loads the folders of its mods folder in name order, and with two
+// mods overriding the same resources that order decides which one wins. A
+// mod's load order is realized as a four-digit prefix of its link name, see
+// links::link_folder_name; mods without one load after every numbered mod.
+// This is the largest order that still fits the prefix.
+pub const MAX_LOAD_ORDER: u32 = 9999;
+
+// Move a mod to the link name `to` gives it. A mod that isn't deployed only
+// loses whatever is left of it under the old name.
+pub fn relink(
+    activator: &dyn Activator,
+    zzmi_path: &str,
+    from: &Mod,
+    to: &Mod,
+) -> Result<(), String> {
+    let deployed = activator.is_active(zzmi_path, from);
+    activator
+        .purge(zzmi_path, from)
+        .map_err(|e| format!("Failed to remove {} from ZZMI: {}", from.title, e))?;
+    if !deployed {
+        return Ok(());
+    }
+    if let Err(e) = activator.activate(zzmi_path, to) {
+        if let Err(restore) = activator.activate(zzmi_path, from) {
+            println!("Failed to restore {}: {}", from.title, restore);
+        }
+        return Err(format!("Failed to add {} to ZZMI: {}", to.title, e));
+    }
+    Ok(())
+}
+
+// Saved toggle states are keyed by the link name unless the ini has a
+// namespace; move them over so the mod keeps them under its new name
+pub async fn carry_saved_values(
+    zzmi_path: &str,
+    mode: ActivationMode,
+    from: &Mod,
+    to: &Mod,
+) -> Result<(), String> {
+    let manifest = manifest_for(to).await?;
+    let old = variable_states(zzmi_path, mode, from, &manifest)?;
+    let new = variable_states(zzmi_path, mode, to, &manifest)?;
+
+    let mut updates = Vec::new();
+    for (old, new) in old.iter().zip(&new) {
+        if old.key != new.key && old.value.is_some() {
+            updates.push((new.key.clone(), old.value.clone()));
+            updates.push((old.key.clone(), None));
+        }
+    }
+    if !updates.is_empty() {
+        write_saved_values(zzmi_path, &updates)?;
+    }
+    Ok(())
+}
+
+pub async fn set_load_order(mod_id: &str, load_order: Option<u32>) -> Result<Mod, String> {
+    if load_order.is_some_and(|order| order > MAX_LOAD_ORDER) {
+        return Err(format!("Load order can be at most {}", MAX_LOAD_ORDER));
+    }
+    let previous = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or("Mod not found")?;
+    let mut m = previous.clone();
+    m.load_order = load_order;
+    if m.load_order == previous.load_order {
+        return Ok(m);
+    }
+
+    let settings = load_settings().await?;
+    let Some(zzmi_path) = settings.zzmi_mods_path else {
+        save_mod_metadata(&m).await?;
+        return Ok(m);
+    };
+    let activator = activator_for(settings.activation_mode);
+    relink(activator.as_ref(), &zzmi_path, &previous, &m)?;
+    if let Err(e) = save_mod_metadata(&m).await {
+        if let Err(restore) = relink(activator.as_ref(), &zzmi_path, &m, &previous) {
+            println!("Failed to restore {}: {}", previous.title, restore);
+        }
+        return Err(e);
+    }
+
+    if let Err(e) = carry_saved_values(&zzmi_path, settings.activation_mode, &previous, &m).await {
+        println!(
+            "Could not carry the toggle states of {} over: {}",
+            m.title, e
+        );
+    }
+    Ok(m)
+}
@@ -0,0 +1,385 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::ini::{parse_ini, read_ini, IniSection, IniSyntaxError};
+use crate::storage::{load_all_mods, load_manifest, save_manifest};
+use crate::variants::deployed_path;
+use crate::Mod;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverrideKind {
+    Texture,
+    Shader,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IniFileManifest {
+    // Relative to the mod folder, with forward slashes
+    pub path: String,
+    pub namespace: Option<String>,
+    pub sections: Vec<String>,
+    pub errors: Vec<IniSyntaxError>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HashOverride {
+    pub ini_file: String,
+    pub section: String,
+    pub kind: OverrideKind,
+    // Lower-case hex, as 3DMigoto matches it
+    pub hash: String,
+    pub match_first_index: Option<String>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceRef {
+    pub ini_file: String,
+    pub section: String,
+    // As written in the ini
+    pub filename: String,
+    // Relative to the mod folder; None for absolute paths or ones that
+    // leave the mod folder
+    pub path: Option<String>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyVariable {
+    pub name: String,
+    // The values the binding cycles through
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyBinding {
+    pub ini_file: String,
+    pub section: String,
+    pub key: Option<String>,
+    pub back: Option<String>,
+    // cycle, toggle or hold; 3DMigoto's default is hold
+    pub binding_type: Option<String>,
+    pub condition: Option<String>,
+    pub variables: Vec<KeyVariable>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModVariable {
+    pub ini_file: String,
+    // Including the leading `$`
+    pub name: String,
+    pub global: bool,
+    pub persist: bool,
+    pub default_value: Option<String>,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModManifest {
+    pub mod_id: String,
+    // Changes whenever an ini file is added, removed or modified
+    pub fingerprint: String,
+    pub parsed_at: String,
+    pub ini_files: Vec<IniFileManifest>,
+    pub overrides: Vec<HashOverride>,
+    pub resources: Vec<ResourceRef>,
+    pub key_bindings: Vec<KeyBinding>,
+    pub variables: Vec<ModVariable>,
+}
+
+// Files and folders XXMI skips
+pub fn is_disabled_name(name: &str) -> bool {
+    name.to_lowercase().starts_with("disabled")
+}
+
+fn collect_ini_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_disabled_name(&name) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_ini_files(&path, out)?;
+        } else if name.to_lowercase().ends_with(".ini") && !name.eq_ignore_ascii_case("desktop.ini")
+        {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+// The folder ini paths are relative to, and every ini file that 3DMigoto would
+// load from the mod, in a stable order. Paths stay relative to the library
+// folder when only one variant of a pack is deployed.
+pub fn mod_ini_files(m: &Mod) -> Result<(PathBuf, Vec<PathBuf>), String> {
+    let (root, files) = ini_files_at(Path::new(&m.file_path))?;
+    let deployed = deployed_path(m);
+    Ok((
+        root,
+        files
+            .into_iter()
+            .filter(|f| f.starts_with(&deployed))
+            .collect(),
+    ))
+}
+
+// Same as `mod_ini_files` for a mod that isn't in the library yet
+pub fn ini_files_at(path: &Path) -> Result<(PathBuf, Vec<PathBuf>), String> {
+    if !path.exists() {
+        return Err(format!("Mod folder {} does not exist", path.display()));
+    }
+
+    if path.is_file() {
+        let root = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let is_ini = path
+            .extension()
+            .map(|e| e.eq_ignore_ascii_case("ini"))
+            .unwrap_or(false);
+        return Ok((
+            root,
+            if is_ini {
+                vec![path.to_path_buf()]
+            } else {
+                vec![]
+            },
+        ));
+    }
+
+    let mut files = Vec::new();
+    collect_ini_files(path, &mut files)?;
+    files.sort();
+    Ok((path.to_path_buf(), files))
+}
+
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+fn fingerprint(root: &Path, files: &[PathBuf]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        relative_path(root, file).hash(&mut hasher);
+        if let Ok(metadata) = fs::metadata(file) {
+            metadata.len().hash(&mut hasher);
+            metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_nanos())
+                .hash(&mut hasher);
+        }
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+// Resolve a `filename =` value against the folder of the ini that names it.
+// Purely lexical: the file does not have to exist.
+pub fn resolve_resource_path(ini_dir: &str, filename: &str) -> Option<String> {
+    let filename = filename.trim().trim_matches('"').replace('\\', "/");
+    if filename.is_empty() || filename.starts_with('/') || filename.as_bytes().get(1) == Some(&b':')
+    {
+        return None;
+    }
+
+    let mut parts: Vec<String> = ini_dir
+        .split('/')
+        .filter(|p| !p.is_empty())
+        .map(str::to_string)
+        .collect();
+    for component in Path::new(&filename).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+// `global persist $swap = 0` -> ($swap, global, persist)
+fn parse_declaration(key: &str) -> Option<(String, bool, bool)> {
+    let words: Vec<String> = key.split_whitespace().map(str::to_lowercase).collect();
+    let (name, modifiers) = words.split_last()?;
+    if !name.starts_with('$') || name.len() < 2 {
+        return None;
+    }
+    if modifiers
+        .iter()
+        .any(|m| m != "global" && m != "persist" && m != "local")
+    {
+        return None;
+    }
+    Some((
+        name.clone(),
+        modifiers.iter().any(|m| m == "global"),
+        modifiers.iter().any(|m| m == "persist"),
+    ))
+}
+
+pub fn override_kind(section: &IniSection) -> Option<OverrideKind> {
+    if section.has_prefix("TextureOverride") {
+        Some(OverrideKind::Texture)
+    } else if section.has_prefix("ShaderOverride") {
+        Some(OverrideKind::Shader)
+    } else {
+        None
+    }
+}
+
+fn key_binding(ini_file: &str, section: &IniSection) -> KeyBinding {
+    let value = |key: &str| section.get(key).map(str::to_string);
+    KeyBinding {
+        ini_file: ini_file.to_string(),
+        section: section.name.clone(),
+        key: value("key"),
+        back: value("back"),
+        binding_type: value("type"),
+        condition: value("condition"),
+        variables: section
+            .entries
+            .iter()
+            .filter(|e| e.key.starts_with('$'))
+            .map(|e| KeyVariable {
+                name: e.key.to_lowercase(),
+                values: e
+                    .value
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect(),
+            })
+            .collect(),
+        line: section.line,
+    }
+}
+
+pub fn build_manifest(m: &Mod) -> Result<ModManifest, String> {
+    let (root, files) = mod_ini_files(m)?;
+    let mut manifest = ModManifest {
+        mod_id: m.id.clone(),
+        fingerprint: fingerprint(&root, &files),
+        parsed_at: Utc::now().to_rfc3339(),
+        ini_files: Vec::new(),
+        overrides: Vec::new(),
+        resources: Vec::new(),
+        key_bindings: Vec::new(),
+        variables: Vec::new(),
+    };
+
+    for file in &files {
+        let ini_file = relative_path(&root, file);
+        let ini_dir = ini_file.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+        let text = read_ini(file).map_err(|e| format!("Failed to read {}: {}", ini_file, e))?;
+        let doc = parse_ini(&text);
+
+        for section in &doc.sections {
+            if let (Some(kind), Some(hash)) = (override_kind(section), section.get("hash")) {
+                manifest.overrides.push(HashOverride {
+                    ini_file: ini_file.clone(),
+                    section: section.name.clone(),
+                    kind,
+                    hash: hash.to_lowercase(),
+                    match_first_index: section.get("match_first_index").map(str::to_string),
+                    line: section.line,
+                });
+            }
+
+            if section.has_prefix("Resource") {
+                for entry in section
+                    .entries
+                    .iter()
+                    .filter(|e| e.key.eq_ignore_ascii_case("filename"))
+                {
+                    manifest.resources.push(ResourceRef {
+                        ini_file: ini_file.clone(),
+                        section: section.name.clone(),
+                        filename: entry.value.clone(),
+                        path: resolve_resource_path(ini_dir, &entry.value),
+                        line: entry.line,
+                    });
+                }
+            }
+
+            if section.has_prefix("Key") && section.get("key").is_some() {
+                manifest.key_bindings.push(key_binding(&ini_file, section));
+            }
+
+            let in_constants = section.name.eq_ignore_ascii_case("Constants");
+            for entry in &section.entries {
+                let Some((name, global, persist)) = parse_declaration(&entry.key) else {
+                    continue;
+                };
+                // Plain `$x = 1` outside [Constants] is an assignment, not a declaration
+                if !global && !persist && !in_constants {
+                    continue;
+                }
+                manifest.variables.push(ModVariable {
+                    ini_file: ini_file.clone(),
+                    name,
+                    global,
+                    persist,
+                    default_value: Some(entry.value.clone()).filter(|v| !v.is_empty()),
+                    line: entry.line,
+                });
+            }
+        }
+
+        manifest.ini_files.push(IniFileManifest {
+            path: ini_file,
+            namespace: doc.namespace().map(str::to_string),
+            sections: doc.sections.iter().map(|s| s.name.clone()).collect(),
+            errors: doc.errors,
+        });
+    }
+
+    Ok(manifest)
+}
+
+// The manifest for a mod, reparsed only when its ini files changed since the
+// cached copy was built
+pub async fn manifest_for(m: &Mod) -> Result<ModManifest, String> {
+    let (root, files) = mod_ini_files(m)?;
+    let current = fingerprint(&root, &files);
+    if let Some(cached) = load_manifest(&m.id).await? {
+        if cached.fingerprint == current {
+            return Ok(cached);
+        }
+    }
+
+    let manifest = build_manifest(m)?;
+    save_manifest(&manifest).await?;
+    Ok(manifest)
+}
+
+pub async fn get_mod_manifest(mod_id: &str) -> Result<ModManifest, String> {
+    let m = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or("Mod not found")?;
+    manifest_for(&m).await
+}
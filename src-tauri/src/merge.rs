@@ -0,0 +1,682 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::activation::refresh_deployment;
+use crate::ini::{parse_ini, read_ini, render_entry, IniEntry, IniSection};
+use crate::library::unique_destination;
+use crate::links::{build_link_name, sanitize_link_part};
+use crate::lint::{lint_added, AddedMod};
+use crate::manifest::{manifest_for, mod_ini_files, override_kind, relative_path};
+use crate::originals::discard_originals;
+use crate::storage::{
+    load_all_mods, load_merge_definition, save_merge_definition, save_mod_metadata,
+};
+use crate::{storage_folder_for, Mod};
+
+// A merged mod holds a copy of each source mod's files in a numbered folder
+// and one generated `merged.ini` that cycles through them with a key, the way
+// the community merge script does. Every section and variable of a source is
+// renamed per variant; overrides of the same hash are combined into one
+// section with an `if $swapvar == n` branch per variant.
+
+const MERGED_INI: &str = "merged.ini";
+const SWAP_VARIABLE: &str = "$swapvar";
+const DEFAULT_SWAP_KEY: &str = "VK_UP";
+
+// Override keys that select what is overridden rather than run when it is
+const OVERRIDE_HEADER_KEYS: [&str; 3] = ["hash", "filter_index", "allow_duplicate_hash"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeDefinition {
+    // Source mod ids, in variant order
+    pub sources: Vec<String>,
+    pub key: String,
+    pub back: Option<String>,
+    // Manifest fingerprint of each source when the mod was generated
+    pub fingerprints: Vec<String>,
+    pub generated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergedModInfo {
+    pub definition: MergeDefinition,
+    // Sources whose ini files changed since the merged mod was generated
+    pub outdated_sources: Vec<String>,
+    // Sources that are no longer in the library
+    pub missing_sources: Vec<String>,
+}
+
+fn is_header_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    OVERRIDE_HEADER_KEYS.contains(&key.as_str()) || key.starts_with("match_")
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+// Renames one source ini's sections and variables so they can't collide with
+// another variant's
+struct Renamer {
+    variant: usize,
+    // Lower-case section name -> new name
+    sections: HashMap<String, String>,
+    // `\ns\` of the source's own namespace, which merged.ini doesn't keep
+    namespace: Option<Vec<char>>,
+}
+
+impl Renamer {
+    fn new(
+        variant: usize,
+        file_index: usize,
+        doc_sections: &[IniSection],
+        namespace: Option<&str>,
+    ) -> Self {
+        let suffix = if file_index == 0 {
+            format!("{}", variant)
+        } else {
+            format!("{}_{}", variant, file_index)
+        };
+        Renamer {
+            variant,
+            sections: doc_sections
+                .iter()
+                .map(|s| (s.name.to_lowercase(), format!("{}_{}", s.name, suffix)))
+                .collect(),
+            namespace: namespace
+                .map(|ns| format!("\\{}\\", ns.trim_matches('\\')).chars().collect()),
+        }
+    }
+
+    // Length of the own-namespace prefix starting at `chars[i]`, if there is one
+    fn own_namespace_at(&self, chars: &[char], i: usize) -> Option<usize> {
+        let namespace = self.namespace.as_ref()?;
+        let rest = chars.get(i..i + namespace.len())?;
+        rest.iter()
+            .zip(namespace)
+            .all(|(a, b)| a.eq_ignore_ascii_case(b))
+            .then_some(namespace.len())
+    }
+
+    fn section(&self, name: &str) -> String {
+        self.sections
+            .get(&name.to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+
+    // Rewrite `$var` and section names appearing in `line`. References through
+    // the source's own namespace (`\ns\Resource`, `$\ns\var`) lose the prefix
+    // and are renamed like local ones; other namespaces are left alone.
+    fn line(&self, line: &str) -> String {
+        let mut chars: Vec<char> = line.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            match self.own_namespace_at(&chars, i) {
+                Some(len) => {
+                    chars.drain(i..i + len);
+                }
+                None => i += 1,
+            }
+        }
+
+        let mut out = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let after_backslash = i > 0 && chars[i - 1] == '\\';
+            if c == '$' && chars.get(i + 1).is_some_and(|n| is_identifier_char(*n)) {
+                let start = i + 1;
+                let mut end = start;
+                while end < chars.len() && is_identifier_char(chars[end]) {
+                    end += 1;
+                }
+                let name: String = chars[start..end].iter().collect();
+                out.push_str(&format!("${}_{}", name, self.variant));
+                i = end;
+            } else if is_identifier_char(c) {
+                let mut end = i;
+                while end < chars.len() && is_identifier_char(chars[end]) {
+                    end += 1;
+                }
+                let word: String = chars[i..end].iter().collect();
+                match self.sections.get(&word.to_lowercase()) {
+                    Some(renamed) if !after_backslash => out.push_str(renamed),
+                    _ => out.push_str(&word),
+                }
+                i = end;
+            } else {
+                out.push(c);
+                i += 1;
+            }
+        }
+        out
+    }
+
+    fn entry(&self, entry: &IniEntry) -> String {
+        self.line(&render_entry(entry))
+    }
+}
+
+// Overrides of one hash, combined across variants
+struct MergedOverride {
+    name: String,
+    header: Vec<String>,
+    branches: Vec<(usize, Vec<String>)>,
+}
+
+#[derive(Default)]
+struct MergedIni {
+    constants: Vec<String>,
+    present: Vec<(usize, Vec<String>)>,
+    // Keyed by section kind, hash and match_first_index
+    overrides: Vec<(String, MergedOverride)>,
+    sections: Vec<(String, Vec<String>)>,
+}
+
+impl MergedIni {
+    fn add_override(
+        &mut self,
+        group: String,
+        name: String,
+        header: Vec<String>,
+        branch: (usize, Vec<String>),
+    ) {
+        match self.overrides.iter_mut().find(|(g, _)| *g == group) {
+            Some((_, merged)) => merged.branches.push(branch),
+            None => self.overrides.push((
+                group,
+                MergedOverride {
+                    name,
+                    header,
+                    branches: vec![branch],
+                },
+            )),
+        }
+    }
+
+    // Fold one ini file of variant `variant` in. `prefix` is the folder its
+    // resources now live in, relative to merged.ini.
+    fn add_file(&mut self, variant: usize, file_index: usize, text: &str, prefix: &str) {
+        let doc = parse_ini(text);
+        let renamer = Renamer::new(variant, file_index, &doc.sections, doc.namespace());
+
+        for section in &doc.sections {
+            let name = section.name.to_lowercase();
+            if name == "constants" {
+                self.constants
+                    .extend(section.entries.iter().map(|e| renamer.entry(e)));
+                continue;
+            }
+            if name == "present" {
+                let body = section.entries.iter().map(|e| renamer.entry(e)).collect();
+                self.present.push((variant, body));
+                continue;
+            }
+
+            if let (Some(kind), Some(hash)) = (override_kind(section), section.get("hash")) {
+                let group = format!(
+                    "{:?}:{}:{}",
+                    kind,
+                    hash.to_lowercase(),
+                    section.get("match_first_index").unwrap_or("")
+                );
+                let (header, body): (Vec<&IniEntry>, Vec<&IniEntry>) =
+                    section.entries.iter().partition(|e| is_header_key(&e.key));
+                self.add_override(
+                    group,
+                    renamer.section(&section.name),
+                    header.into_iter().map(render_entry).collect(),
+                    (
+                        variant,
+                        body.into_iter().map(|e| renamer.entry(e)).collect(),
+                    ),
+                );
+                continue;
+            }
+
+            let mut lines = Vec::new();
+            let mut has_condition = false;
+            for entry in &section.entries {
+                let key = entry.key.to_lowercase();
+                if key == "filename" && section.has_prefix("Resource") {
+                    let path = format!("{}{}", prefix, entry.value.trim().trim_matches('"'));
+                    lines.push(format!("filename = {}", path.replace('/', "\\")));
+                } else if key == "condition" && section.has_prefix("Key") {
+                    has_condition = true;
+                    lines.push(format!(
+                        "condition = {} == {} && ({})",
+                        SWAP_VARIABLE,
+                        variant,
+                        renamer.line(&entry.value)
+                    ));
+                } else {
+                    lines.push(renamer.entry(entry));
+                }
+            }
+            // A variant's own keys only work while it is the one shown
+            if section.has_prefix("Key") && !has_condition {
+                lines.insert(0, format!("condition = {} == {}", SWAP_VARIABLE, variant));
+            }
+            self.sections.push((renamer.section(&section.name), lines));
+        }
+    }
+
+    fn render(&self, titles: &[&str], key: &str, back: Option<&str>) -> String {
+        let mut out = Vec::new();
+        out.push(format!(
+            "; Generated by Aether Manager from: {}",
+            titles.join(", ")
+        ));
+        out.push("; Regenerate the mod in Aether instead of editing this file".to_string());
+        out.push(String::new());
+
+        out.push("[Constants]".to_string());
+        out.push(format!("global persist {} = 0", SWAP_VARIABLE));
+        out.push("global $active = 0".to_string());
+        out.extend(self.constants.iter().cloned());
+        out.push(String::new());
+
+        out.push("[KeySwap]".to_string());
+        out.push("condition = $active == 1".to_string());
+        out.push(format!("key = {}", key));
+        if let Some(back) = back {
+            out.push(format!("back = {}", back));
+        }
+        out.push("type = cycle".to_string());
+        let values: Vec<String> = (0..titles.len()).map(|i| i.to_string()).collect();
+        out.push(format!("{} = {}", SWAP_VARIABLE, values.join(",")));
+        out.push(String::new());
+
+        out.push("[Present]".to_string());
+        out.push("post $active = 0".to_string());
+        push_branches(&mut out, &self.present);
+        out.push(String::new());
+
+        for (_, merged) in &self.overrides {
+            out.push(format!("[{}]", merged.name));
+            out.extend(merged.header.iter().cloned());
+            out.push("$active = 1".to_string());
+            push_branches(&mut out, &merged.branches);
+            out.push(String::new());
+        }
+
+        for (name, lines) in &self.sections {
+            out.push(format!("[{}]", name));
+            out.extend(lines.iter().cloned());
+            out.push(String::new());
+        }
+
+        out.join("\r\n")
+    }
+}
+
+fn push_branches(out: &mut Vec<String>, branches: &[(usize, Vec<String>)]) {
+    for (variant, body) in branches.iter().filter(|(_, body)| !body.is_empty()) {
+        out.push(format!("if {} == {}", SWAP_VARIABLE, variant));
+        out.extend(body.iter().map(|line| format!("    {}", line)));
+        out.push("endif".to_string());
+    }
+}
+
+// Copy a source mod into the merged folder, leaving its ini files behind;
+// merged.ini replaces them
+fn copy_resources(src: &Path, dst: &Path) -> Result<(), String> {
+    fs::create_dir_all(dst).map_err(|e| format!("Failed to create {}: {}", dst.display(), e))?;
+    let entries =
+        fs::read_dir(src).map_err(|e| format!("Failed to read {}: {}", src.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.to_lowercase().starts_with("disabled") {
+            continue;
+        }
+        if path.is_dir() {
+            copy_resources(&path, &dst.join(&name))?;
+        } else if !name.to_lowercase().ends_with(".ini") {
+            fs::copy(&path, dst.join(&name))
+                .map_err(|e| format!("Failed to copy {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+fn variant_folder(index: usize, m: &Mod) -> String {
+    match sanitize_link_part(&m.title) {
+        t if t.is_empty() => format!("{}", index),
+        t => format!("{}_{}", index, t),
+    }
+}
+
+// Write the merged mod for `sources` into `dest`, which must not exist
+fn generate(sources: &[Mod], key: &str, back: Option<&str>, dest: &Path) -> Result<(), String> {
+    let mut merged = MergedIni::default();
+    for (variant, m) in sources.iter().enumerate() {
+        let folder = variant_folder(variant, m);
+        let (root, files) = mod_ini_files(m)?;
+        copy_resources(&root, &dest.join(&folder))?;
+
+        for (file_index, file) in files.iter().enumerate() {
+            let text =
+                read_ini(file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+            let ini_file = relative_path(&root, file);
+            let ini_dir = ini_file.rsplit_once('/').map(|(d, _)| d).unwrap_or("");
+            let prefix = if ini_dir.is_empty() {
+                format!("{}/", folder)
+            } else {
+                format!("{}/{}/", folder, ini_dir)
+            };
+            merged.add_file(variant, file_index, &text, &prefix);
+        }
+    }
+
+    let titles: Vec<&str> = sources.iter().map(|m| m.title.as_str()).collect();
+    fs::write(dest.join(MERGED_INI), merged.render(&titles, key, back))
+        .map_err(|e| format!("Failed to write {}: {}", MERGED_INI, e))
+}
+
+// Generate next to `dest` and swap it in only once everything was written
+fn generate_in_place(
+    sources: &[Mod],
+    key: &str,
+    back: Option<&str>,
+    dest: &Path,
+) -> Result<(), String> {
+    let staging = PathBuf::from(format!("{}.merging", dest.display()));
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .map_err(|e| format!("Failed to clear {}: {}", staging.display(), e))?;
+    }
+    if let Err(e) = generate(sources, key, back, &staging) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(e);
+    }
+    if !dest.exists() {
+        return fs::rename(&staging, dest)
+            .map_err(|e| format!("Failed to move merged mod into place: {}", e));
+    }
+
+    // Keep the current mod until the new one is in its place
+    let previous = PathBuf::from(format!("{}.previous", dest.display()));
+    if previous.exists() {
+        fs::remove_dir_all(&previous)
+            .map_err(|e| format!("Failed to clear {}: {}", previous.display(), e))?;
+    }
+    if let Err(e) = fs::rename(dest, &previous) {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(format!("Failed to replace {}: {}", dest.display(), e));
+    }
+    if let Err(e) = fs::rename(&staging, dest) {
+        let _ = fs::rename(&previous, dest);
+        let _ = fs::remove_dir_all(&staging);
+        return Err(format!("Failed to move merged mod into place: {}", e));
+    }
+    if let Err(e) = fs::remove_dir_all(&previous) {
+        println!("Warning: could not remove {}: {}", previous.display(), e);
+    }
+    Ok(())
+}
+
+// The source mods in the given order; they must all be folder mods for the
+// same character
+fn resolve_sources(mods: &[Mod], mod_ids: &[String]) -> Result<Vec<Mod>, String> {
+    if mod_ids.len() < 2 {
+        return Err("Pick at least two mods to merge".to_string());
+    }
+    let mut sources: Vec<Mod> = Vec::new();
+    for id in mod_ids {
+        if sources.iter().any(|m| &m.id == id) {
+            return Err("A mod can only be merged once".to_string());
+        }
+        let m = mods
+            .iter()
+            .find(|m| &m.id == id)
+            .ok_or_else(|| format!("Mod {} not found", id))?;
+        if !Path::new(&m.file_path).is_dir() {
+            return Err(format!(
+                "{} is not a folder mod and can't be merged",
+                m.title
+            ));
+        }
+        sources.push(m.clone());
+    }
+
+    let character = sources[0].character.clone();
+    if character.is_none() || sources.iter().any(|m| m.character != character) {
+        return Err("Only mods for the same character can be merged".to_string());
+    }
+    Ok(sources)
+}
+
+async fn fingerprints(sources: &[Mod]) -> Result<Vec<String>, String> {
+    let mut out = Vec::new();
+    for m in sources {
+        out.push(manifest_for(m).await?.fingerprint);
+    }
+    Ok(out)
+}
+
+pub async fn create_merged_mod(
+    title: String,
+    mod_ids: Vec<String>,
+    key: Option<String>,
+    back: Option<String>,
+) -> Result<AddedMod, String> {
+    let sources = resolve_sources(&load_all_mods().await?, &mod_ids)?;
+    let character = sources[0].character.clone();
+    let key = key
+        .map(|k| k.trim().to_string())
+        .filter(|k| !k.is_empty())
+        .unwrap_or_else(|| DEFAULT_SWAP_KEY.to_string());
+    let back = back.map(|b| b.trim().to_string()).filter(|b| !b.is_empty());
+
+    let storage_folder = storage_folder_for(character.as_deref())?;
+    let folder_name = match sanitize_link_part(&title) {
+        t if t.is_empty() => "Merged".to_string(),
+        t => t,
+    };
+    let destination = unique_destination(&storage_folder, &folder_name);
+    generate_in_place(&sources, &key, back.as_deref(), Path::new(&destination))?;
+
+    let mut merged = Mod {
+        id: Uuid::new_v4().to_string(),
+        title,
+        description: None,
+        thumbnail: sources.iter().find_map(|m| m.thumbnail.clone()),
+        is_active: false,
+        date_added: Utc::now().to_rfc3339(),
+        character,
+        original_name: Path::new(&destination)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or(folder_name),
+        file_path: destination,
+        is_missing: false,
+        link_name: String::new(),
+        variants: Vec::new(),
+        selected_variant: None,
+        group: None,
+        dependencies: Vec::new(),
+        load_order: None,
+    };
+    merged.link_name = build_link_name(&merged);
+
+    let definition = MergeDefinition {
+        sources: mod_ids,
+        key,
+        back,
+        fingerprints: fingerprints(&sources).await?,
+        generated_at: Utc::now().to_rfc3339(),
+    };
+    save_mod_metadata(&merged).await?;
+    save_merge_definition(&merged.id, &definition).await?;
+    Ok(lint_added(merged))
+}
+
+async fn find_merged(mod_id: &str) -> Result<(Mod, MergeDefinition, Vec<Mod>), String> {
+    let mods = load_all_mods().await?;
+    let merged = mods
+        .iter()
+        .find(|m| m.id == mod_id)
+        .cloned()
+        .ok_or("Mod not found")?;
+    let definition = load_merge_definition(mod_id)
+        .await?
+        .ok_or("This mod was not created by merging mods")?;
+    Ok((merged, definition, mods))
+}
+
+pub async fn get_merged_mod_info(mod_id: &str) -> Result<MergedModInfo, String> {
+    let (_, definition, mods) = find_merged(mod_id).await?;
+    let mut info = MergedModInfo {
+        definition,
+        outdated_sources: Vec::new(),
+        missing_sources: Vec::new(),
+    };
+    for (id, fingerprint) in info
+        .definition
+        .sources
+        .iter()
+        .zip(&info.definition.fingerprints)
+    {
+        let Some(m) = mods.iter().find(|m| &m.id == id) else {
+            info.missing_sources.push(id.clone());
+            continue;
+        };
+        match manifest_for(m).await {
+            Ok(manifest) if &manifest.fingerprint == fingerprint => {}
+            Ok(_) => info.outdated_sources.push(id.clone()),
+            Err(_) => info.missing_sources.push(id.clone()),
+        }
+    }
+    Ok(info)
+}
+
+// Rebuild a merged mod from the current state of its sources. Edits made to
+// merged.ini itself are discarded along with their kept originals.
+pub async fn regenerate_merged_mod(mod_id: &str) -> Result<Mod, String> {
+    let (merged, mut definition, mods) = find_merged(mod_id).await?;
+    let sources = resolve_sources(&mods, &definition.sources)?;
+
+    generate_in_place(
+        &sources,
+        &definition.key,
+        definition.back.as_deref(),
+        Path::new(&merged.file_path),
+    )?;
+    discard_originals(&merged.id)?;
+
+    definition.fingerprints = fingerprints(&sources).await?;
+    definition.generated_at = Utc::now().to_rfc3339();
+    save_merge_definition(&merged.id, &definition).await?;
+    refresh_deployment(&merged).await?;
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn renamer(namespace: Option<&str>) -> Renamer {
+        let doc = parse_ini("[ResourceBody]\n[CommandListSwap]\n");
+        Renamer::new(1, 0, &doc.sections, namespace)
+    }
+
+    #[test]
+    fn line_renames_variables_and_sections() {
+        let renamer = renamer(None);
+        assert_eq!(
+            renamer.line("if $swap == 1 && $\\other\\swap == 0"),
+            "if $swap_1 == 1 && $\\other\\swap == 0"
+        );
+        assert_eq!(
+            renamer.line("ps-t0 = ResourceBody"),
+            "ps-t0 = ResourceBody_1"
+        );
+        assert_eq!(
+            renamer.line("run = \\other\\CommandListSwap"),
+            "run = \\other\\CommandListSwap"
+        );
+    }
+
+    #[test]
+    fn line_drops_own_namespace() {
+        let renamer = renamer(Some("Ellen\\Maid"));
+        assert_eq!(
+            renamer.line("run = \\ellen\\maid\\CommandListSwap"),
+            "run = CommandListSwap_1"
+        );
+        assert_eq!(renamer.line("$\\Ellen\\Maid\\swap = 1"), "$swap_1 = 1");
+        assert_eq!(
+            renamer.line("ps-t0 = \\Ellen\\Other\\ResourceBody"),
+            "ps-t0 = \\Ellen\\Other\\ResourceBody"
+        );
+    }
+
+    #[test]
+    fn overrides_of_one_hash_are_grouped() {
+        let mut merged = MergedIni::default();
+        merged.add_file(
+            0,
+            0,
+            "[TextureOverrideBody]\nhash = 1a2b3c4d\nps-t0 = ResourceBody\n[ResourceBody]\nfilename = Body.dds\n",
+            "0_A/",
+        );
+        merged.add_file(
+            1,
+            0,
+            "[TextureOverrideTorso]\nhash = 1A2B3C4D\nps-t0 = ResourceBody\n[TextureOverrideFace]\nhash = 99999999\nps-t0 = ResourceBody\n[ResourceBody]\nfilename = tex/Body.dds\n",
+            "1_B/sub/",
+        );
+        assert_eq!(merged.overrides.len(), 2);
+        let body = &merged.overrides[0].1;
+        assert_eq!(body.name, "TextureOverrideBody_0");
+        assert_eq!(body.header, ["hash = 1a2b3c4d"]);
+        assert_eq!(
+            body.branches,
+            [
+                (0, vec!["ps-t0 = ResourceBody_0".to_string()]),
+                (1, vec!["ps-t0 = ResourceBody_1".to_string()]),
+            ]
+        );
+
+        let text = merged.render(&["A", "B"], "VK_UP", None);
+        assert!(text.contains("[ResourceBody_0]\r\nfilename = 0_A\\Body.dds\r\n"));
+        assert!(text.contains("[ResourceBody_1]\r\nfilename = 1_B\\sub\\tex\\Body.dds\r\n"));
+        assert!(text.contains(
+            "[TextureOverrideBody_0]\r\nhash = 1a2b3c4d\r\n$active = 1\r\nif $swapvar == 0\r\n    ps-t0 = ResourceBody_0\r\nendif\r\nif $swapvar == 1\r\n    ps-t0 = ResourceBody_1\r\nendif\r\n"
+        ));
+        assert!(text.contains("$swapvar = 0,1\r\n"));
+    }
+
+    #[test]
+    fn key_sections_only_work_for_their_variant() {
+        let mut merged = MergedIni::default();
+        merged.add_file(
+            2,
+            1,
+            "[KeyHat]\nkey = VK_H\ntype = cycle\n$hat = 0,1\n[KeyGlow]\ncondition = $hat == 1\nkey = VK_G\n",
+            "2_C/",
+        );
+        assert_eq!(merged.sections[0].0, "KeyHat_2_1");
+        assert_eq!(
+            merged.sections[0].1,
+            [
+                "condition = $swapvar == 2",
+                "key = VK_H",
+                "type = cycle",
+                "$hat_2 = 0,1"
+            ]
+        );
+        assert_eq!(
+            merged.sections[1].1,
+            ["condition = $swapvar == 2 && ($hat_2 == 1)", "key = VK_G"]
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::get_app_config_dir;
+use crate::ini::{apply_edits, IniEdit};
+use crate::manifest::{mod_ini_files, relative_path};
+use crate::Mod;
+
+// Before Aether edits an ini file in the library, the untouched file is kept
+// in `mods/ini-backups/<mod id>/<path in the mod>`. Only the first version is
+// kept, so reverting always goes back to the file as it was installed.
+
+fn backup_dir(mod_id: &str) -> Result<PathBuf, String> {
+    Ok(get_app_config_dir()?
+        .join("mods")
+        .join("ini-backups")
+        .join(mod_id))
+}
+
+// Keep the original of `file` (an ini file below `root`) unless one is
+// already kept
+pub fn keep_original(m: &Mod, root: &Path, file: &Path) -> Result<(), String> {
+    let backup = backup_dir(&m.id)?.join(relative_path(root, file));
+    if backup.exists() {
+        return Ok(());
+    }
+    if let Some(parent) = backup.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create backup directory: {}", e))?;
+    }
+    fs::copy(file, &backup).map_err(|e| format!("Failed to back up {}: {}", file.display(), e))?;
+    Ok(())
+}
+
+// Put files back as they were before this edit
+fn undo_writes(written: &[(&PathBuf, Vec<u8>)]) {
+    for (path, before) in written.iter().rev() {
+        if let Err(e) = fs::write(path, before) {
+            println!("Failed to restore {}: {}", path.display(), e);
+        }
+    }
+}
+
+// Apply `edits` to ini files of a mod, keeping each original first. All files
+// are written or none is; a mod is never left half-edited.
+pub fn edit_ini_files(
+    m: &Mod,
+    root: &Path,
+    edits: &[(PathBuf, Vec<IniEdit>)],
+) -> Result<(), String> {
+    let mut written = Vec::new();
+    for (path, file_edits) in edits {
+        let before = keep_original(m, root, path).and_then(|_| {
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+        });
+        let before = match before {
+            Ok(before) => before,
+            Err(e) => {
+                undo_writes(&written);
+                return Err(e);
+            }
+        };
+        let result = fs::write(path, apply_edits(&before, file_edits))
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e));
+        written.push((path, before));
+        if let Err(e) = result {
+            undo_writes(&written);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+fn collect_files(dir: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read backups: {}", e))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("Failed to read backups: {}", e))?
+            .path();
+        if path.is_dir() {
+            collect_files(&path, out)?;
+        } else {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+// Put every kept original back into the mod folder and forget the backups.
+// Returns the restored paths, relative to the mod folder.
+pub fn restore_originals(m: &Mod) -> Result<Vec<String>, String> {
+    let dir = backup_dir(&m.id)?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let (root, _) = mod_ini_files(m)?;
+
+    let mut files = Vec::new();
+    collect_files(&dir, &mut files)?;
+    files.sort();
+
+    let mut restored = Vec::new();
+    for backup in files {
+        let relative = relative_path(&dir, &backup);
+        let target = root.join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to restore {}: {}", relative, e))?;
+        }
+        fs::copy(&backup, &target).map_err(|e| format!("Failed to restore {}: {}", relative, e))?;
+        restored.push(relative);
+    }
+
+    discard_originals(&m.id)?;
+    Ok(restored)
+}
+
+pub fn discard_originals(mod_id: &str) -> Result<(), String> {
+    let dir = backup_dir(mod_id)?;
+    if dir.exists() {
+        fs::remove_dir_all(&dir).map_err(|e| format!("Failed to remove ini backups: {}", e))?;
+    }
+    Ok(())
+}
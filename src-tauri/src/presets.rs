@@ -0,0 +1,430 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::activation::{activator_for, Activator};
+use crate::conflicts::{conflicts_introduced, HashConflict};
+use crate::dependencies::with_dependencies;
+use crate::exclusive::{effective_preset, SlotConflict};
+use crate::links::{inspect_link_path, link_path_for, LinkConflict};
+use crate::load_order::{carry_saved_values, relink};
+use crate::storage::{load_all_mods, load_all_presets, load_settings, save_all_mods};
+use crate::variables::restore_variables;
+use crate::AppSettings;
+use crate::{Mod, Preset};
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedLink {
+    pub mod_id: String,
+    pub title: String,
+    pub link_path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlannedConflict {
+    pub mod_id: String,
+    pub title: String,
+    pub conflict: LinkConflict,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresetPlan {
+    pub preset_id: String,
+    pub to_activate: Vec<PlannedLink>,
+    pub to_deactivate: Vec<PlannedLink>,
+    // Mods already in the state the preset wants
+    pub unchanged: Vec<String>,
+    // Preset entries that are no longer in the library, or whose folder is gone
+    pub missing_mods: Vec<String>,
+    // Activations blocked by content we don't own; the plan can't run with these
+    pub conflicts: Vec<PlannedConflict>,
+    // Hash overrides the activations would collide on; a warning only
+    pub hash_conflicts: Vec<HashConflict>,
+    // Captured toggle states written to d3dx_user.ini along with the mods
+    pub variables_to_restore: usize,
+    // Preset mods left out because another mod of their exclusive slot comes first
+    pub slot_conflicts: Vec<SlotConflict>,
+    // Mods the preset's mods need that are applied along with them
+    pub added_dependencies: Vec<String>,
+    // Deployed mods moved to the link name of the load order the preset gives them
+    pub reordered: Vec<PlannedLink>,
+}
+
+fn planned_link(zzmi_path: &str, m: &Mod) -> PlannedLink {
+    PlannedLink {
+        mod_id: m.id.clone(),
+        title: m.title.clone(),
+        link_path: link_path_for(zzmi_path, m),
+    }
+}
+
+pub async fn find_preset(preset_id: &str) -> Result<Preset, String> {
+    load_all_presets()
+        .await?
+        .into_iter()
+        .find(|p| p.id == preset_id)
+        .ok_or_else(|| "Preset not found".to_string())
+}
+
+// The preset as it can be applied: the first mod of each exclusive slot,
+// plus whatever those mods depend on
+fn applicable_preset(
+    preset: &Preset,
+    mods: &[Mod],
+    zzmi_path: &str,
+    settings: &AppSettings,
+) -> Result<(Preset, Vec<SlotConflict>, Vec<String>), String> {
+    let (mut preset, slot_conflicts) = effective_preset(settings, mods, preset);
+    let (mod_ids, added) = with_dependencies(settings, zzmi_path, mods, &preset.mod_ids)
+        .map_err(|e| format!("Preset {} cannot be applied: {}", preset.name, e))?;
+    preset.mod_ids = mod_ids;
+    Ok((preset, slot_conflicts, added))
+}
+
+// The library with the preset's mods in the load order it recorded for them;
+// mods it has no order for and dependencies it brought in keep their own
+fn with_preset_load_order(preset: &Preset, mods: &[Mod], added: &[String]) -> Vec<Mod> {
+    mods.iter()
+        .map(|m| {
+            let mut m = m.clone();
+            if let Some(order) = preset.load_order.get(&m.id) {
+                if preset.mod_ids.contains(&m.id) && !added.contains(&m.id) {
+                    m.load_order = Some(*order);
+                }
+            }
+            m
+        })
+        .collect()
+}
+
+// Mods the plan would activate that are deployed already, just under the
+// link name of another load order, are moved instead
+fn split_reordered(
+    plan: &mut PresetPlan,
+    previous: &[Mod],
+    zzmi_path: &str,
+    activator: &dyn Activator,
+) {
+    let (reordered, to_activate) =
+        std::mem::take(&mut plan.to_activate)
+            .into_iter()
+            .partition(|link| {
+                previous
+                    .iter()
+                    .any(|m| m.id == link.mod_id && activator.is_active(zzmi_path, m))
+            });
+    plan.reordered = reordered;
+    plan.to_activate = to_activate;
+}
+
+async fn zzmi_settings() -> Result<(String, AppSettings), String> {
+    let settings = load_settings().await?;
+    let zzmi_path = settings
+        .zzmi_mods_path
+        .clone()
+        .ok_or("ZZMI mods path not configured. Please set it in settings.")?;
+    Ok((zzmi_path, settings))
+}
+
+// Work out what applying `preset` would change, based on what is actually
+// deployed in the ZZMI folder. Nothing is modified.
+pub fn compute_plan(
+    preset: &Preset,
+    mods: &[Mod],
+    zzmi_path: &str,
+    activator: &dyn Activator,
+) -> PresetPlan {
+    let desired: HashSet<&str> = preset.mod_ids.iter().map(String::as_str).collect();
+    let known: HashSet<&str> = mods.iter().map(|m| m.id.as_str()).collect();
+
+    let mut plan = PresetPlan {
+        preset_id: preset.id.clone(),
+        to_activate: Vec::new(),
+        to_deactivate: Vec::new(),
+        unchanged: Vec::new(),
+        missing_mods: preset
+            .mod_ids
+            .iter()
+            .filter(|id| !known.contains(id.as_str()))
+            .cloned()
+            .collect(),
+        conflicts: Vec::new(),
+        hash_conflicts: Vec::new(),
+        variables_to_restore: preset
+            .variables
+            .iter()
+            .filter(|v| known.contains(v.mod_id.as_str()))
+            .count(),
+        slot_conflicts: Vec::new(),
+        added_dependencies: Vec::new(),
+        reordered: Vec::new(),
+    };
+
+    for m in mods {
+        let should_be_active = desired.contains(m.id.as_str());
+        let linked = activator.is_active(zzmi_path, m);
+
+        if should_be_active && !linked {
+            if !Path::new(&m.file_path).exists() {
+                plan.missing_mods.push(m.id.clone());
+                continue;
+            }
+            let link = planned_link(zzmi_path, m);
+            if let Some(conflict) = inspect_link_path(Path::new(&link.link_path)) {
+                plan.conflicts.push(PlannedConflict {
+                    mod_id: m.id.clone(),
+                    title: m.title.clone(),
+                    conflict,
+                });
+            } else {
+                plan.to_activate.push(link);
+            }
+        } else if !should_be_active && linked {
+            plan.to_deactivate.push(planned_link(zzmi_path, m));
+        } else {
+            plan.unchanged.push(m.id.clone());
+        }
+    }
+
+    plan
+}
+
+// Hash conflicts the plan's activations bring in with the mods that stay active
+async fn introduced_hash_conflicts(
+    preset: &Preset,
+    plan: &PresetPlan,
+    mods: &[Mod],
+) -> Vec<HashConflict> {
+    let added_ids: HashSet<&str> = plan.to_activate.iter().map(|l| l.mod_id.as_str()).collect();
+    let blocked: HashSet<&str> = plan
+        .missing_mods
+        .iter()
+        .map(String::as_str)
+        .chain(plan.conflicts.iter().map(|c| c.mod_id.as_str()))
+        .collect();
+
+    let staying: Vec<&Mod> = mods
+        .iter()
+        .filter(|m| preset.mod_ids.contains(&m.id))
+        .filter(|m| !added_ids.contains(m.id.as_str()) && !blocked.contains(m.id.as_str()))
+        .collect();
+    let added: Vec<&Mod> = mods
+        .iter()
+        .filter(|m| added_ids.contains(m.id.as_str()))
+        .collect();
+
+    conflicts_introduced(&staying, &added).await
+}
+
+pub async fn plan_preset(preset_id: &str) -> Result<PresetPlan, String> {
+    let mods = load_all_mods().await?;
+    let (zzmi_path, settings) = zzmi_settings().await?;
+    let (preset, slot_conflicts, added) =
+        applicable_preset(&find_preset(preset_id).await?, &mods, &zzmi_path, &settings)?;
+    let activator = activator_for(settings.activation_mode);
+    let ordered = with_preset_load_order(&preset, &mods, &added);
+    let mut plan = compute_plan(&preset, &ordered, &zzmi_path, activator.as_ref());
+    split_reordered(&mut plan, &mods, &zzmi_path, activator.as_ref());
+    plan.slot_conflicts = slot_conflicts;
+    plan.added_dependencies = added;
+    plan.hash_conflicts = introduced_hash_conflicts(&preset, &plan, &ordered).await;
+    Ok(plan)
+}
+
+#[derive(Debug)]
+enum AppliedStep<'a> {
+    Activated(&'a PlannedLink),
+    Deactivated(&'a PlannedLink),
+    Reordered(&'a PlannedLink),
+}
+
+// Undo already applied steps in reverse order. Failures are logged; there is
+// nothing better to do with them while unwinding.
+fn rollback(
+    applied: &[AppliedStep],
+    activator: &dyn Activator,
+    zzmi_path: &str,
+    by_id: &HashMap<String, Mod>,
+    previous: &HashMap<String, Mod>,
+) {
+    for step in applied.iter().rev() {
+        let Some(m) = by_id.get(&link_of(step).mod_id) else {
+            continue;
+        };
+        let result = match step {
+            AppliedStep::Activated(_) => activator
+                .deactivate(zzmi_path, m)
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            AppliedStep::Deactivated(_) => {
+                activator.activate(zzmi_path, m).map_err(|e| e.to_string())
+            }
+            AppliedStep::Reordered(link) => match previous.get(&link.mod_id) {
+                Some(old) => relink(activator, zzmi_path, m, old),
+                None => Ok(()),
+            },
+        };
+        if let Err(e) = result {
+            println!("Rollback failed for {}: {}", link_of(step).link_path, e);
+        }
+    }
+}
+
+fn link_of<'a>(step: &AppliedStep<'a>) -> &'a PlannedLink {
+    match step {
+        AppliedStep::Activated(link)
+        | AppliedStep::Deactivated(link)
+        | AppliedStep::Reordered(link) => link,
+    }
+}
+
+// Apply a preset in two phases: plan first, then execute every step, rolling
+// back the ones already done if any fails. Mod records are committed once, at
+// the end, so disk and metadata never disagree.
+pub async fn apply_preset(preset_id: &str) -> Result<PresetPlan, String> {
+    let mut mods = load_all_mods().await?;
+    let (zzmi_path, settings) = zzmi_settings().await?;
+    let (preset, slot_conflicts, added) =
+        applicable_preset(&find_preset(preset_id).await?, &mods, &zzmi_path, &settings)?;
+    let activator = activator_for(settings.activation_mode);
+    fs::create_dir_all(&zzmi_path)
+        .map_err(|e| format!("Failed to create ZZMI mods directory: {}", e))?;
+
+    let previous: HashMap<String, Mod> = mods.iter().map(|m| (m.id.clone(), m.clone())).collect();
+    mods = with_preset_load_order(&preset, &mods, &added);
+    let mut plan = compute_plan(&preset, &mods, &zzmi_path, activator.as_ref());
+    let before: Vec<Mod> = previous.values().cloned().collect();
+    split_reordered(&mut plan, &before, &zzmi_path, activator.as_ref());
+    if !plan.conflicts.is_empty() {
+        let titles: Vec<&str> = plan.conflicts.iter().map(|c| c.title.as_str()).collect();
+        return Err(format!(
+            "Preset cannot be applied: the ZZMI folder has unmanaged content where these mods link: {}",
+            titles.join(", ")
+        ));
+    }
+
+    plan.slot_conflicts = slot_conflicts;
+    plan.added_dependencies = added;
+    for conflict in &plan.slot_conflicts {
+        println!(
+            "Preset {} has several mods for {}; using {}",
+            preset.name, conflict.slot, conflict.kept
+        );
+    }
+    plan.hash_conflicts = introduced_hash_conflicts(&preset, &plan, &mods).await;
+    for conflict in &plan.hash_conflicts {
+        println!(
+            "Preset {} activates mods that override hash {}",
+            preset.name, conflict.hash
+        );
+    }
+
+    let by_id: HashMap<String, Mod> = mods.iter().map(|m| (m.id.clone(), m.clone())).collect();
+    let mut applied = Vec::new();
+
+    for link in &plan.to_deactivate {
+        let result = match by_id.get(&link.mod_id) {
+            Some(m) => activator.deactivate(&zzmi_path, m),
+            None => Ok(false),
+        };
+        if let Err(e) = result {
+            rollback(&applied, activator.as_ref(), &zzmi_path, &by_id, &previous);
+            return Err(format!("Failed to remove mod from ZZMI: {}", e));
+        }
+        applied.push(AppliedStep::Deactivated(link));
+    }
+
+    for link in &plan.reordered {
+        let (Some(old), Some(m)) = (previous.get(&link.mod_id), by_id.get(&link.mod_id)) else {
+            continue;
+        };
+        if let Err(e) = relink(activator.as_ref(), &zzmi_path, old, m) {
+            rollback(&applied, activator.as_ref(), &zzmi_path, &by_id, &previous);
+            return Err(e);
+        }
+        applied.push(AppliedStep::Reordered(link));
+    }
+
+    for link in &plan.to_activate {
+        let Some(m) = by_id.get(&link.mod_id) else {
+            continue;
+        };
+        if let Err(e) = activator.activate(&zzmi_path, m) {
+            rollback(&applied, activator.as_ref(), &zzmi_path, &by_id, &previous);
+            return Err(format!(
+                "Failed to add mod to ZZMI for {}: {}",
+                link.title, e
+            ));
+        }
+        applied.push(AppliedStep::Activated(link));
+    }
+
+    // Single metadata commit: every mod ends up recorded as the preset wants,
+    // except those we could not activate
+    let desired: HashSet<&str> = preset.mod_ids.iter().map(String::as_str).collect();
+    let missing: HashSet<&str> = plan.missing_mods.iter().map(String::as_str).collect();
+    let mut changed = Vec::new();
+    for m in mods.iter_mut() {
+        let is_active = desired.contains(m.id.as_str()) && !missing.contains(m.id.as_str());
+        let reordered = previous
+            .get(&m.id)
+            .is_some_and(|old| old.load_order != m.load_order);
+        if m.is_active != is_active || reordered {
+            m.is_active = is_active;
+            changed.push(m.clone());
+        }
+    }
+
+    if let Err(e) = save_all_mods(&changed).await {
+        rollback(&applied, activator.as_ref(), &zzmi_path, &by_id, &previous);
+        return Err(e);
+    }
+
+    // Saved toggle states follow the mods to their new link names, and
+    // leftovers under the old names go
+    for m in &changed {
+        let Some(old) = previous
+            .get(&m.id)
+            .filter(|old| old.load_order != m.load_order)
+        else {
+            continue;
+        };
+        if let Err(e) = activator.purge(&zzmi_path, old) {
+            println!(
+                "Failed to remove the old deployment of {}: {}",
+                old.title, e
+            );
+        }
+        if let Err(e) = carry_saved_values(&zzmi_path, settings.activation_mode, old, m).await {
+            println!(
+                "Could not carry the toggle states of {} over: {}",
+                m.title, e
+            );
+        }
+    }
+
+    // Toggle states go last; they are keyed by where each mod is deployed
+    let in_preset: Vec<Mod> = mods
+        .into_iter()
+        .filter(|m| desired.contains(m.id.as_str()) && !missing.contains(m.id.as_str()))
+        .collect();
+    plan.variables_to_restore = restore_variables(
+        &zzmi_path,
+        settings.activation_mode,
+        &in_preset,
+        &preset.variables,
+    )
+    .await
+    .map_err(|e| {
+        format!(
+            "Preset applied, but its toggle states could not be restored: {}",
+            e
+        )
+    })?;
+
+    Ok(plan)
+}
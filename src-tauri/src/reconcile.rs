@@ -0,0 +1,205 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::activation::{activator_for, Activator};
+use crate::links::{inspect_link_path, link_path_for, link_points_to};
+use crate::storage::{load_all_mods, load_settings, save_all_mods};
+use crate::Mod;
+
+// How the ZZMI folder disagrees with what we recorded for a mod
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftKind {
+    // Recorded active, but nothing is at the link path
+    Missing,
+    // Our link is there but its target no longer exists
+    Broken,
+    // Our link is there but points at another library folder
+    WrongTarget,
+    // Something we don't own occupies the link path
+    Foreign,
+    // Recorded inactive, yet the mod is deployed
+    Unexpected,
+    // Recorded active at a link path another mod's deployment occupies
+    SharedLink,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriftAction {
+    // Only reported; nothing was changed
+    None,
+    // The mod was deployed again to match the record
+    Relinked,
+    // The record was changed to inactive
+    Deactivated,
+    // The record was changed to active
+    Activated,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkDrift {
+    pub mod_id: String,
+    pub title: String,
+    pub link_path: String,
+    pub kind: DriftKind,
+    pub action: DriftAction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    pub checked: usize,
+    pub drift: Vec<LinkDrift>,
+}
+
+fn detect_drift(
+    activator: &dyn Activator,
+    zzmi_path: &str,
+    link_path: &Path,
+    m: &Mod,
+) -> Option<DriftKind> {
+    let deployed = activator.is_active(zzmi_path, m);
+
+    if !m.is_active {
+        return deployed.then_some(DriftKind::Unexpected);
+    }
+
+    if deployed {
+        None
+    } else if fs::symlink_metadata(link_path).is_err() {
+        Some(DriftKind::Missing)
+    } else if inspect_link_path(link_path).is_some() {
+        Some(DriftKind::Foreign)
+    } else if link_points_to(link_path, m) {
+        Some(DriftKind::Broken)
+    } else {
+        // Another mod's link or copy, or one left by a different mode
+        Some(DriftKind::WrongTarget)
+    }
+}
+
+// Two active mods must never share one link path. When they do, only the mod
+// the link actually points at keeps it; returns the ids of the others.
+fn shared_link_losers(activator: &dyn Activator, zzmi_path: &str, mods: &[Mod]) -> HashSet<String> {
+    let mut owners: HashMap<String, Vec<&Mod>> = HashMap::new();
+    for m in mods.iter().filter(|m| m.is_active) {
+        owners
+            .entry(link_path_for(zzmi_path, m))
+            .or_default()
+            .push(m);
+    }
+
+    let mut losers = HashSet::new();
+    for sharing in owners.into_values().filter(|s| s.len() > 1) {
+        let owner = sharing
+            .iter()
+            .find(|m| activator.is_active(zzmi_path, m))
+            .map(|m| m.id.clone());
+        losers.extend(
+            sharing
+                .into_iter()
+                .filter(|m| Some(&m.id) != owner.as_ref())
+                .map(|m| m.id.clone()),
+        );
+    }
+    losers
+}
+
+fn repair_drift(
+    activator: &dyn Activator,
+    zzmi_path: &str,
+    m: &mut Mod,
+    kind: DriftKind,
+) -> DriftAction {
+    match kind {
+        DriftKind::Unexpected => {
+            m.is_active = true;
+            DriftAction::Activated
+        }
+        DriftKind::Foreign | DriftKind::SharedLink => {
+            // Never touch what isn't ours; the record follows the disk instead
+            m.is_active = false;
+            DriftAction::Deactivated
+        }
+        DriftKind::Missing | DriftKind::Broken | DriftKind::WrongTarget => {
+            if !Path::new(&m.file_path).exists() {
+                let _ = activator.deactivate(zzmi_path, m);
+                m.is_active = false;
+                return DriftAction::Deactivated;
+            }
+            match activator.activate(zzmi_path, m) {
+                Ok(()) => DriftAction::Relinked,
+                Err(e) => {
+                    println!("Failed to relink {}: {}", m.title, e);
+                    m.is_active = false;
+                    DriftAction::Deactivated
+                }
+            }
+        }
+    }
+}
+
+// Compare every mod's recorded `is_active` with what is deployed in the ZZMI
+// folder. With `repair`, mods are redeployed where possible and the records
+// of the rest are corrected; otherwise drift is only reported.
+pub async fn reconcile_active_state(repair: bool) -> Result<ReconcileReport, String> {
+    let mut mods = load_all_mods().await?;
+    let settings = load_settings().await?;
+    let Some(zzmi_path) = settings.zzmi_mods_path else {
+        return Ok(ReconcileReport {
+            checked: 0,
+            drift: Vec::new(),
+        });
+    };
+
+    let mut report = ReconcileReport {
+        checked: mods.len(),
+        drift: Vec::new(),
+    };
+    let activator = activator_for(settings.activation_mode);
+    let losers = shared_link_losers(activator.as_ref(), &zzmi_path, &mods);
+    let mut changed = Vec::new();
+
+    for m in mods.iter_mut() {
+        let link_path = link_path_for(&zzmi_path, m);
+        let kind = if losers.contains(&m.id) {
+            Some(DriftKind::SharedLink)
+        } else {
+            detect_drift(activator.as_ref(), &zzmi_path, Path::new(&link_path), m)
+        };
+        let Some(kind) = kind else {
+            continue;
+        };
+
+        let action = if repair {
+            let action = repair_drift(activator.as_ref(), &zzmi_path, m, kind);
+            if matches!(action, DriftAction::Activated | DriftAction::Deactivated) {
+                changed.push(m.clone());
+            }
+            action
+        } else {
+            DriftAction::None
+        };
+
+        println!(
+            "Link drift for {} at {}: {:?} ({:?})",
+            m.title, link_path, kind, action
+        );
+        report.drift.push(LinkDrift {
+            mod_id: m.id.clone(),
+            title: m.title.clone(),
+            link_path,
+            kind,
+            action,
+        });
+    }
+
+    if !changed.is_empty() {
+        save_all_mods(&changed).await?;
+    }
+    Ok(report)
+}
@@ -0,0 +1,913 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::manifest::ModManifest;
+use crate::merge::MergeDefinition;
+use crate::{get_app_config_dir, AppSettings, Mod, Preset, PresetVariable};
+
+// Bump this and add a step to `migrate` whenever the schema changes
+const SCHEMA_VERSION: i32 = 10;
+
+// Number of timestamped database backups kept in `mods/backups`
+const MAX_BACKUPS: usize = 10;
+
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%3f";
+
+static CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryBackup {
+    pub name: String,
+    pub created_at: Option<String>,
+    pub size_bytes: u64,
+}
+
+fn library_dir() -> Result<PathBuf, String> {
+    Ok(get_app_config_dir()?.join("mods"))
+}
+
+fn database_path() -> Result<PathBuf, String> {
+    Ok(library_dir()?.join("library.db"))
+}
+
+fn backups_dir() -> Result<PathBuf, String> {
+    Ok(library_dir()?.join("backups"))
+}
+
+fn open_connection() -> Result<Connection, String> {
+    let dir = library_dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create app data directory: {}", e))?;
+
+    let path = database_path()?;
+    let existed = path.exists();
+    let mut conn = if existed {
+        match open_checked(&path) {
+            Ok(conn) => conn,
+            Err(reason) => recover_database(&path, &reason)?,
+        }
+    } else {
+        Connection::open(&path).map_err(|e| format!("Failed to open library database: {}", e))?
+    };
+
+    conn.execute_batch(
+        "PRAGMA journal_mode = WAL;
+         PRAGMA foreign_keys = ON;
+         PRAGMA busy_timeout = 5000;",
+    )
+    .map_err(|e| format!("Failed to configure library database: {}", e))?;
+
+    // Snapshot the library before a migration touches it, and otherwise at
+    // most once a day so restarts don't push the useful backups out
+    if existed && (schema_version(&conn)? < SCHEMA_VERSION || !backed_up_recently()) {
+        if let Err(e) = backup_database(&conn) {
+            println!("Warning: failed to back up library database: {}", e);
+        }
+    }
+
+    migrate(&mut conn)?;
+    Ok(conn)
+}
+
+// Run `f` against the shared connection. Access is serialized, so concurrent
+// Tauri commands can no longer interleave their writes.
+pub fn with_db<T>(f: impl FnOnce(&mut Connection) -> Result<T, String>) -> Result<T, String> {
+    let mut guard = CONNECTION.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_none() {
+        *guard = Some(open_connection()?);
+    }
+
+    let conn = guard
+        .as_mut()
+        .ok_or("Library database is not initialized")?;
+    f(conn)
+}
+
+// Run `f` inside a single transaction, committing only if it succeeds
+pub fn with_transaction<T>(
+    f: impl FnOnce(&Transaction) -> Result<T, String>,
+) -> Result<T, String> {
+    with_db(|conn| {
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let value = f(&tx)?;
+        tx.commit()
+            .map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(value)
+    })
+}
+
+// ===== Schema =====
+fn schema_version(conn: &Connection) -> Result<i32, String> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read schema version: {}", e))
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), String> {
+    let version = schema_version(conn)?;
+
+    if version > SCHEMA_VERSION {
+        return Err(format!(
+            "Library database schema v{} is newer than this app supports (v{})",
+            version, SCHEMA_VERSION
+        ));
+    }
+    if version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Failed to start migration: {}", e))?;
+
+    if version < 1 {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS mods (
+                 id TEXT PRIMARY KEY,
+                 title TEXT NOT NULL,
+                 description TEXT,
+                 thumbnail TEXT,
+                 is_active INTEGER NOT NULL DEFAULT 0,
+                 date_added TEXT NOT NULL,
+                 character TEXT,
+                 file_path TEXT NOT NULL,
+                 original_name TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS presets (
+                 id TEXT PRIMARY KEY,
+                 name TEXT NOT NULL,
+                 created_at TEXT NOT NULL,
+                 updated_at TEXT NOT NULL
+             );
+             CREATE TABLE IF NOT EXISTS preset_mods (
+                 preset_id TEXT NOT NULL REFERENCES presets(id) ON DELETE CASCADE,
+                 mod_id TEXT NOT NULL,
+                 position INTEGER NOT NULL,
+                 PRIMARY KEY (preset_id, mod_id)
+             );
+             CREATE TABLE IF NOT EXISTS settings (
+                 key TEXT PRIMARY KEY,
+                 value TEXT NOT NULL
+             );",
+        )
+        .map_err(|e| format!("Failed to create library schema: {}", e))?;
+    }
+
+    if version < 2 {
+        tx.execute_batch("ALTER TABLE mods ADD COLUMN is_missing INTEGER NOT NULL DEFAULT 0;")
+            .map_err(|e| format!("Failed to migrate library schema to v2: {}", e))?;
+    }
+
+    if version < 3 {
+        tx.execute_batch("ALTER TABLE mods ADD COLUMN link_name TEXT NOT NULL DEFAULT '';")
+            .map_err(|e| format!("Failed to migrate library schema to v3: {}", e))?;
+    }
+
+    if version < 4 {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS mod_manifests (
+                 mod_id TEXT PRIMARY KEY REFERENCES mods(id) ON DELETE CASCADE,
+                 manifest TEXT NOT NULL
+             );",
+        )
+        .map_err(|e| format!("Failed to migrate library schema to v4: {}", e))?;
+    }
+
+    if version < 5 {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS preset_variables (
+                 preset_id TEXT NOT NULL REFERENCES presets(id) ON DELETE CASCADE,
+                 mod_id TEXT NOT NULL,
+                 ini_file TEXT NOT NULL,
+                 name TEXT NOT NULL,
+                 value TEXT,
+                 PRIMARY KEY (preset_id, mod_id, ini_file, name)
+             );",
+        )
+        .map_err(|e| format!("Failed to migrate library schema to v5: {}", e))?;
+    }
+
+    if version < 6 {
+        tx.execute_batch(
+            "CREATE TABLE IF NOT EXISTS merged_mods (
+                 mod_id TEXT PRIMARY KEY REFERENCES mods(id) ON DELETE CASCADE,
+                 definition TEXT NOT NULL
+             );",
+        )
+        .map_err(|e| format!("Failed to migrate library schema to v6: {}", e))?;
+    }
+
+    if version < 7 {
+        tx.execute_batch(
+            "ALTER TABLE mods ADD COLUMN variants TEXT NOT NULL DEFAULT '[]';
+             ALTER TABLE mods ADD COLUMN selected_variant TEXT;",
+        )
+        .map_err(|e| format!("Failed to migrate library schema to v7: {}", e))?;
+    }
+
+    if version < 8 {
+        tx.execute_batch("ALTER TABLE mods ADD COLUMN mod_group TEXT;")
+            .map_err(|e| format!("Failed to migrate library schema to v8: {}", e))?;
+    }
+
+    if version < 9 {
+        tx.execute_batch("ALTER TABLE mods ADD COLUMN dependencies TEXT NOT NULL DEFAULT '[]';")
+            .map_err(|e| format!("Failed to migrate library schema to v9: {}", e))?;
+    }
+
+    if version < 10 {
+        tx.execute_batch(
+            "ALTER TABLE mods ADD COLUMN load_order INTEGER;
+             ALTER TABLE preset_mods ADD COLUMN load_order INTEGER;",
+        )
+        .map_err(|e| format!("Failed to migrate library schema to v10: {}", e))?;
+    }
+
+    // Legacy data is imported against the final schema
+    if version < 1 {
+        import_legacy_json(&tx)?;
+    }
+
+    tx.pragma_update(None, "user_version", SCHEMA_VERSION)
+        .map_err(|e| format!("Failed to update schema version: {}", e))?;
+    tx.commit()
+        .map_err(|e| format!("Failed to commit migration: {}", e))?;
+
+    // Only retire the JSON files once their contents are safely committed
+    retire_legacy_json()?;
+    Ok(())
+}
+
+fn legacy_json_files() -> Result<[PathBuf; 3], String> {
+    let library = library_dir()?;
+    Ok([
+        library.join("mods.json"),
+        library.join("presets.json"),
+        get_app_config_dir()?.join("settings.json"),
+    ])
+}
+
+fn read_legacy_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Option<T>, String> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+}
+
+// A legacy file that can't be read is set aside, so it doesn't keep the
+// library from opening on every launch
+fn load_legacy_json<T: serde::de::DeserializeOwned>(path: &Path) -> Option<T> {
+    match read_legacy_json(path) {
+        Ok(value) => value,
+        Err(e) => {
+            println!("Warning: skipping legacy data: {}", e);
+            if let Err(e) = fs::rename(path, sidecar_path(path, ".corrupt")) {
+                println!("Warning: failed to set aside {}: {}", path.display(), e);
+            }
+            None
+        }
+    }
+}
+
+// One-time import of mods.json, presets.json and settings.json
+fn import_legacy_json(tx: &Transaction) -> Result<(), String> {
+    let [mods_path, presets_path, settings_path] = legacy_json_files()?;
+
+    if let Some(mods) = load_legacy_json::<Vec<Mod>>(&mods_path) {
+        println!("Migrating {} mods from {}", mods.len(), mods_path.display());
+        for m in &mods {
+            upsert_mod(tx, m)?;
+        }
+    }
+
+    if let Some(presets) = load_legacy_json::<Vec<Preset>>(&presets_path) {
+        println!(
+            "Migrating {} presets from {}",
+            presets.len(),
+            presets_path.display()
+        );
+        for p in &presets {
+            upsert_preset(tx, p)?;
+        }
+    }
+
+    if let Some(settings) = load_legacy_json::<AppSettings>(&settings_path) {
+        println!("Migrating settings from {}", settings_path.display());
+        write_settings(tx, &settings)?;
+    }
+
+    Ok(())
+}
+
+fn retire_legacy_json() -> Result<(), String> {
+    for path in legacy_json_files()? {
+        if path.exists() {
+            fs::rename(&path, sidecar_path(&path, ".migrated"))
+                .map_err(|e| format!("Failed to retire {}: {}", path.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+// ===== Mods =====
+const MOD_COLUMNS: &str = "id, title, description, thumbnail, is_active, date_added, character, \
+     file_path, original_name, is_missing, link_name, variants, selected_variant, mod_group, \
+     dependencies, load_order";
+
+fn mod_from_row(row: &Row) -> rusqlite::Result<Mod> {
+    Ok(Mod {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        thumbnail: row.get(3)?,
+        is_active: row.get(4)?,
+        date_added: row.get(5)?,
+        character: row.get(6)?,
+        file_path: row.get(7)?,
+        original_name: row.get(8)?,
+        is_missing: row.get(9)?,
+        link_name: row.get(10)?,
+        // Stored as a JSON list
+        variants: serde_json::from_str(&row.get::<_, String>(11)?).unwrap_or_default(),
+        selected_variant: row.get(12)?,
+        group: row.get(13)?,
+        dependencies: serde_json::from_str(&row.get::<_, String>(14)?).unwrap_or_default(),
+        load_order: row.get(15)?,
+    })
+}
+
+fn upsert_mod(conn: &Connection, m: &Mod) -> Result<(), String> {
+    conn.execute(
+        &format!(
+            "INSERT INTO mods ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+             ON CONFLICT(id) DO UPDATE SET
+                 title = excluded.title,
+                 description = excluded.description,
+                 thumbnail = excluded.thumbnail,
+                 is_active = excluded.is_active,
+                 date_added = excluded.date_added,
+                 character = excluded.character,
+                 file_path = excluded.file_path,
+                 original_name = excluded.original_name,
+                 is_missing = excluded.is_missing,
+                 link_name = excluded.link_name,
+                 variants = excluded.variants,
+                 selected_variant = excluded.selected_variant,
+                 mod_group = excluded.mod_group,
+                 dependencies = excluded.dependencies,
+                 load_order = excluded.load_order",
+            MOD_COLUMNS
+        ),
+        params![
+            m.id,
+            m.title,
+            m.description,
+            m.thumbnail,
+            m.is_active,
+            m.date_added,
+            m.character,
+            m.file_path,
+            m.original_name,
+            m.is_missing,
+            m.link_name,
+            serde_json::to_string(&m.variants).unwrap_or_else(|_| "[]".to_string()),
+            m.selected_variant,
+            m.group,
+            serde_json::to_string(&m.dependencies).unwrap_or_else(|_| "[]".to_string()),
+            m.load_order,
+        ],
+    )
+    .map_err(|e| format!("Failed to save mod {}: {}", m.id, e))?;
+    Ok(())
+}
+
+pub async fn load_all_mods() -> Result<Vec<Mod>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare(&format!("SELECT {} FROM mods ORDER BY rowid", MOD_COLUMNS))
+            .map_err(|e| format!("Failed to read mods database: {}", e))?;
+        let mods = stmt
+            .query_map([], mod_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| format!("Failed to read mods database: {}", e))?;
+        Ok(mods)
+    })
+}
+
+pub async fn save_mod_metadata(new_mod: &Mod) -> Result<(), String> {
+    with_db(|conn| upsert_mod(conn, new_mod))
+}
+
+pub async fn remove_mod_metadata(mod_id: &str) -> Result<(), String> {
+    with_transaction(|tx| {
+        tx.execute("DELETE FROM mods WHERE id = ?1", params![mod_id])
+            .map_err(|e| format!("Failed to remove mod: {}", e))?;
+        tx.execute("DELETE FROM preset_mods WHERE mod_id = ?1", params![mod_id])
+            .map_err(|e| format!("Failed to remove mod from presets: {}", e))?;
+        tx.execute(
+            "DELETE FROM preset_variables WHERE mod_id = ?1",
+            params![mod_id],
+        )
+        .map_err(|e| format!("Failed to remove mod from presets: {}", e))?;
+        Ok(())
+    })
+}
+
+// Persist every given mod in one transaction. Mods that are not in the list
+// are left untouched, so a stale snapshot cannot drop a concurrent install.
+pub async fn save_all_mods(mods: &[Mod]) -> Result<(), String> {
+    with_transaction(|tx| {
+        for m in mods {
+            upsert_mod(tx, m)?;
+        }
+        Ok(())
+    })
+}
+
+// ===== Manifests =====
+pub async fn load_manifest(mod_id: &str) -> Result<Option<ModManifest>, String> {
+    let stored: Option<String> = with_db(|conn| {
+        conn.query_row(
+            "SELECT manifest FROM mod_manifests WHERE mod_id = ?1",
+            params![mod_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read mod manifest: {}", e))
+    })?;
+
+    // A manifest from an older app version is simply rebuilt
+    Ok(stored.and_then(|json| serde_json::from_str(&json).ok()))
+}
+
+pub async fn save_manifest(manifest: &ModManifest) -> Result<(), String> {
+    let json = serde_json::to_string(manifest)
+        .map_err(|e| format!("Failed to serialize mod manifest: {}", e))?;
+    with_db(|conn| {
+        conn.execute(
+            "INSERT INTO mod_manifests (mod_id, manifest) VALUES (?1, ?2)
+             ON CONFLICT(mod_id) DO UPDATE SET manifest = excluded.manifest",
+            params![manifest.mod_id, json],
+        )
+        .map_err(|e| format!("Failed to save mod manifest: {}", e))?;
+        Ok(())
+    })
+}
+
+// ===== Merged mods =====
+pub async fn load_merge_definition(mod_id: &str) -> Result<Option<MergeDefinition>, String> {
+    let stored: Option<String> = with_db(|conn| {
+        conn.query_row(
+            "SELECT definition FROM merged_mods WHERE mod_id = ?1",
+            params![mod_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read merged mod: {}", e))
+    })?;
+
+    stored
+        .map(|json| {
+            serde_json::from_str(&json).map_err(|e| format!("Failed to parse merged mod: {}", e))
+        })
+        .transpose()
+}
+
+pub async fn save_merge_definition(
+    mod_id: &str,
+    definition: &MergeDefinition,
+) -> Result<(), String> {
+    let json = serde_json::to_string(definition)
+        .map_err(|e| format!("Failed to serialize merged mod: {}", e))?;
+    with_db(|conn| {
+        conn.execute(
+            "INSERT INTO merged_mods (mod_id, definition) VALUES (?1, ?2)
+             ON CONFLICT(mod_id) DO UPDATE SET definition = excluded.definition",
+            params![mod_id, json],
+        )
+        .map_err(|e| format!("Failed to save merged mod: {}", e))?;
+        Ok(())
+    })
+}
+
+// ===== Presets =====
+fn upsert_preset(conn: &Connection, preset: &Preset) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO presets (id, name, created_at, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET
+             name = excluded.name,
+             created_at = excluded.created_at,
+             updated_at = excluded.updated_at",
+        params![preset.id, preset.name, preset.created_at, preset.updated_at],
+    )
+    .map_err(|e| format!("Failed to save preset {}: {}", preset.id, e))?;
+
+    conn.execute(
+        "DELETE FROM preset_mods WHERE preset_id = ?1",
+        params![preset.id],
+    )
+    .map_err(|e| format!("Failed to save preset mods: {}", e))?;
+
+    for (position, mod_id) in preset.mod_ids.iter().enumerate() {
+        conn.execute(
+            "INSERT OR IGNORE INTO preset_mods (preset_id, mod_id, position, load_order)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                preset.id,
+                mod_id,
+                position as i64,
+                preset.load_order.get(mod_id)
+            ],
+        )
+        .map_err(|e| format!("Failed to save preset mods: {}", e))?;
+    }
+
+    conn.execute(
+        "DELETE FROM preset_variables WHERE preset_id = ?1",
+        params![preset.id],
+    )
+    .map_err(|e| format!("Failed to save preset variables: {}", e))?;
+
+    // Values of mods that left the preset are dropped with them
+    for v in preset
+        .variables
+        .iter()
+        .filter(|v| preset.mod_ids.contains(&v.mod_id))
+    {
+        conn.execute(
+            "INSERT OR REPLACE INTO preset_variables (preset_id, mod_id, ini_file, name, value)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![preset.id, v.mod_id, v.ini_file, v.name, v.value],
+        )
+        .map_err(|e| format!("Failed to save preset variables: {}", e))?;
+    }
+
+    Ok(())
+}
+
+fn preset_mod_ids(conn: &Connection, preset_id: &str) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT mod_id FROM preset_mods WHERE preset_id = ?1 ORDER BY position",
+    )?;
+    let ids = stmt
+        .query_map(params![preset_id], |row| row.get(0))?
+        .collect();
+    ids
+}
+
+fn preset_load_order(
+    conn: &Connection,
+    preset_id: &str,
+) -> rusqlite::Result<BTreeMap<String, u32>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT mod_id, load_order FROM preset_mods
+         WHERE preset_id = ?1 AND load_order IS NOT NULL",
+    )?;
+    let load_order = stmt
+        .query_map(params![preset_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect();
+    load_order
+}
+
+fn preset_variables(conn: &Connection, preset_id: &str) -> rusqlite::Result<Vec<PresetVariable>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT mod_id, ini_file, name, value FROM preset_variables
+         WHERE preset_id = ?1 ORDER BY rowid",
+    )?;
+    let variables = stmt
+        .query_map(params![preset_id], |row| {
+            Ok(PresetVariable {
+                mod_id: row.get(0)?,
+                ini_file: row.get(1)?,
+                name: row.get(2)?,
+                value: row.get(3)?,
+            })
+        })?
+        .collect();
+    variables
+}
+
+pub async fn load_all_presets() -> Result<Vec<Preset>, String> {
+    with_db(|conn| {
+        let mut stmt = conn
+            .prepare("SELECT id, name, created_at, updated_at FROM presets ORDER BY rowid")
+            .map_err(|e| format!("Failed to read presets DB: {}", e))?;
+        let presets = stmt
+            .query_map([], |row| {
+                let id: String = row.get(0)?;
+                Ok(Preset {
+                    mod_ids: preset_mod_ids(conn, &id)?,
+                    variables: preset_variables(conn, &id)?,
+                    load_order: preset_load_order(conn, &id)?,
+                    id,
+                    name: row.get(1)?,
+                    created_at: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            })
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| format!("Failed to read presets DB: {}", e))?;
+        Ok(presets)
+    })
+}
+
+pub async fn save_preset(preset: &Preset) -> Result<(), String> {
+    with_transaction(|tx| upsert_preset(tx, preset))
+}
+
+// Returns false when no preset with that id exists
+pub async fn remove_preset(preset_id: &str) -> Result<bool, String> {
+    with_db(|conn| {
+        let removed = conn
+            .execute("DELETE FROM presets WHERE id = ?1", params![preset_id])
+            .map_err(|e| format!("Failed to delete preset: {}", e))?;
+        Ok(removed > 0)
+    })
+}
+
+// ===== Settings =====
+// Each top-level settings field is stored as its own JSON-encoded row, so new
+// fields fall back to their serde defaults on older databases.
+fn write_settings(conn: &Connection, settings: &AppSettings) -> Result<(), String> {
+    let value = serde_json::to_value(settings)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    let fields = value
+        .as_object()
+        .ok_or("Failed to serialize settings: expected an object")?;
+
+    for (key, value) in fields {
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value.to_string()],
+        )
+        .map_err(|e| format!("Failed to write settings: {}", e))?;
+    }
+
+    Ok(())
+}
+
+pub async fn load_settings() -> Result<AppSettings, String> {
+    let stored = with_db(|conn| {
+        let has_settings: Option<i64> = conn
+            .query_row("SELECT 1 FROM settings LIMIT 1", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("Failed to read settings: {}", e))?;
+        if has_settings.is_none() {
+            return Ok(None);
+        }
+
+        let mut stmt = conn
+            .prepare("SELECT key, value FROM settings")
+            .map_err(|e| format!("Failed to read settings: {}", e))?;
+        let rows = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(|e| format!("Failed to read settings: {}", e))?;
+
+        let mut fields = serde_json::Map::new();
+        for (key, value) in rows {
+            let value = serde_json::from_str(&value)
+                .map_err(|e| format!("Failed to parse setting {}: {}", key, e))?;
+            fields.insert(key, value);
+        }
+        serde_json::from_value(serde_json::Value::Object(fields))
+            .map(Some)
+            .map_err(|e| format!("Failed to parse settings: {}", e))
+    })?;
+
+    match stored {
+        Some(settings) => Ok(settings),
+        None => {
+            // Create default settings with app-managed paths
+            let default_settings = AppSettings {
+                zzmi_mods_path: None,
+                activation_mode: Default::default(),
+                exclusive_slots: Vec::new(),
+            };
+            save_settings(&default_settings).await?;
+            Ok(default_settings)
+        }
+    }
+}
+
+pub async fn save_settings(settings: &AppSettings) -> Result<(), String> {
+    with_transaction(|tx| write_settings(tx, settings))
+}
+
+// ===== Backups & recovery =====
+fn open_checked(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(path).map_err(|e| format!("cannot open: {}", e))?;
+    let status: String = conn
+        .query_row("PRAGMA quick_check", [], |row| row.get(0))
+        .map_err(|e| format!("integrity check failed: {}", e))?;
+    if status != "ok" {
+        return Err(format!("integrity check failed: {}", status));
+    }
+    Ok(conn)
+}
+
+fn backup_timestamp() -> String {
+    Utc::now().format(BACKUP_TIMESTAMP_FORMAT).to_string()
+}
+
+fn sidecar_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// Newest first; the timestamp in the file name sorts chronologically
+fn list_backup_files() -> Result<Vec<PathBuf>, String> {
+    let dir = backups_dir()?;
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(&dir)
+        .map_err(|e| format!("Failed to read backups directory: {}", e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("library-") && n.ends_with(".db"))
+                .unwrap_or(false)
+        })
+        .collect();
+    backups.sort();
+    backups.reverse();
+    Ok(backups)
+}
+
+fn prune_backups() -> Result<(), String> {
+    for old in list_backup_files()?.into_iter().skip(MAX_BACKUPS) {
+        fs::remove_file(&old)
+            .map_err(|e| format!("Failed to remove old backup {}: {}", old.display(), e))?;
+    }
+    Ok(())
+}
+
+// Write a consistent copy of the database into the backups folder, going
+// through a temp file so a crash never leaves a half-written backup behind
+fn backup_database(conn: &Connection) -> Result<PathBuf, String> {
+    let dir = backups_dir()?;
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create backups directory: {}", e))?;
+
+    let target = dir.join(format!("library-{}.db", backup_timestamp()));
+    let temp = sidecar_path(&target, ".tmp");
+    if temp.exists() {
+        fs::remove_file(&temp).map_err(|e| format!("Failed to clear stale backup: {}", e))?;
+    }
+
+    conn.execute("VACUUM INTO ?1", params![temp.to_string_lossy()])
+        .map_err(|e| format!("Failed to write backup: {}", e))?;
+    fs::rename(&temp, &target).map_err(|e| format!("Failed to finalize backup: {}", e))?;
+
+    prune_backups()?;
+    println!("Backed up library database to {}", target.display());
+    Ok(target)
+}
+
+// Stale WAL pages of an old database must never be replayed onto another one
+fn remove_wal_files(path: &Path) -> Result<(), String> {
+    for suffix in ["-wal", "-shm"] {
+        let sidecar = sidecar_path(path, suffix);
+        if sidecar.exists() {
+            fs::remove_file(&sidecar)
+                .map_err(|e| format!("Failed to remove {}: {}", sidecar.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+// Copy `backup` next to the database, so nothing that happens to the backups
+// folder in the meantime can take it away
+fn stage_backup(backup: &Path, path: &Path) -> Result<PathBuf, String> {
+    let staged = sidecar_path(path, ".restore");
+    fs::copy(backup, &staged).map_err(|e| format!("Failed to copy backup: {}", e))?;
+    Ok(staged)
+}
+
+// Replace the database file with a staged backup in one rename
+fn install_backup(staged: &Path, path: &Path) -> Result<(), String> {
+    remove_wal_files(path)?;
+    fs::rename(staged, path).map_err(|e| format!("Failed to restore backup: {}", e))
+}
+
+// The database could not be opened or failed its integrity check. Keep the
+// damaged file for inspection and fall back to the newest healthy backup.
+fn recover_database(path: &Path, reason: &str) -> Result<Connection, String> {
+    println!("Library database is damaged ({}), attempting recovery", reason);
+
+    let corrupt = sidecar_path(path, &format!(".corrupt-{}", backup_timestamp()));
+    fs::rename(path, &corrupt)
+        .map_err(|e| format!("Failed to move damaged database aside: {}", e))?;
+    println!("Damaged database kept at {}", corrupt.display());
+
+    for backup in list_backup_files()? {
+        if let Err(e) = open_checked(&backup) {
+            println!("Skipping backup {}: {}", backup.display(), e);
+            continue;
+        }
+        install_backup(&stage_backup(&backup, path)?, path)?;
+        println!("Restored library database from {}", backup.display());
+        return open_checked(path).map_err(|e| format!("Failed to open restored database: {}", e));
+    }
+
+    println!("No healthy backup found, starting with an empty library database");
+    remove_wal_files(path)?;
+    Connection::open(path).map_err(|e| format!("Failed to open library database: {}", e))
+}
+
+fn backup_created_at(path: &Path) -> Option<DateTime<Utc>> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .and_then(|n| n.strip_prefix("library-"))
+        .and_then(|n| n.strip_suffix(".db"))
+        .and_then(|ts| NaiveDateTime::parse_from_str(ts, BACKUP_TIMESTAMP_FORMAT).ok())
+        .map(|ts| DateTime::<Utc>::from_naive_utc_and_offset(ts, Utc))
+}
+
+// Whether the newest backup is less than a day old
+fn backed_up_recently() -> bool {
+    list_backup_files()
+        .ok()
+        .and_then(|backups| backups.first().and_then(|p| backup_created_at(p)))
+        .is_some_and(|at| Utc::now() - at < chrono::Duration::days(1))
+}
+
+fn describe_backup(path: &Path) -> Result<LibraryBackup, String> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or_default()
+        .to_string();
+    let created_at = backup_created_at(path).map(|at| at.to_rfc3339());
+    let size_bytes = fs::metadata(path)
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to read backup {}: {}", name, e))?;
+
+    Ok(LibraryBackup {
+        name,
+        created_at,
+        size_bytes,
+    })
+}
+
+pub async fn list_backups() -> Result<Vec<LibraryBackup>, String> {
+    list_backup_files()?
+        .iter()
+        .map(|path| describe_backup(path))
+        .collect()
+}
+
+pub async fn create_backup() -> Result<LibraryBackup, String> {
+    let path = with_db(|conn| backup_database(conn))?;
+    describe_backup(&path)
+}
+
+// Swap the live database for one of the backups. The current state is backed
+// up first, so a restore can itself be undone.
+pub async fn restore_backup(name: &str) -> Result<(), String> {
+    let backup = list_backup_files()?
+        .into_iter()
+        .find(|p| p.file_name().and_then(|n| n.to_str()) == Some(name))
+        .ok_or("Backup not found")?;
+    open_checked(&backup).map_err(|e| format!("Backup {} is not usable: {}", name, e))?;
+
+    // Staged before backing up the current state, which may prune the very
+    // backup being restored
+    let path = database_path()?;
+    let staged = stage_backup(&backup, &path)?;
+
+    let mut guard = CONNECTION.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(conn) = guard.take() {
+        if let Err(e) = backup_database(&conn) {
+            *guard = Some(conn);
+            let _ = fs::remove_file(&staged);
+            return Err(e);
+        }
+        if let Err((conn, e)) = conn.close() {
+            *guard = Some(conn);
+            let _ = fs::remove_file(&staged);
+            return Err(format!("Failed to close library database: {}", e));
+        }
+    }
+
+    let installed = install_backup(&staged, &path);
+    *guard = Some(open_connection()?);
+    installed?;
+    println!("Restored library database from {}", backup.display());
+    Ok(())
+}
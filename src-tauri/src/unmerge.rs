@@ -0,0 +1,457 @@
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use uuid::Uuid;
+
+use crate::ini::{apply_edits, parse_ini, read_ini, IniDocument, IniEdit};
+use crate::library::unique_destination;
+use crate::links::{build_link_name, sanitize_link_part};
+use crate::lint::{lint_added, AddedMod};
+use crate::manifest::{mod_ini_files, relative_path};
+use crate::storage::{load_all_mods, remove_mod_metadata, save_mod_metadata};
+use crate::{storage_folder_for, Mod};
+
+// A merged mod switches variants with a key that cycles one variable, and
+// wraps each variant's draw calls in `if $var == n`. Splitting it evaluates
+// those branches for one value at a time and writes the result next to the
+// original, which is not modified.
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapVariable {
+    // Including the leading `$`, lower-case
+    pub name: String,
+    // The values its key cycles through, each one variant
+    pub values: Vec<String>,
+    // How many `if`/`elif` branches test it
+    pub branches: usize,
+}
+
+// `$swapvar == 2` -> ("$swapvar", 2.0)
+fn parse_comparison(condition: &str) -> Option<(String, f64)> {
+    let cleaned: String = condition
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '(' && *c != ')')
+        .collect();
+    let (name, value) = cleaned.split_once("==")?;
+    if !name.starts_with('$') || name.len() < 2 {
+        return None;
+    }
+    Some((name.to_lowercase(), value.parse().ok()?))
+}
+
+// The condition of an `if`, `elif` or `else if` entry
+fn branch_condition(key: &str, value: &str) -> Option<String> {
+    match key.to_lowercase().as_str() {
+        "if" | "elif" => Some(value.to_string()),
+        "else" => value
+            .strip_prefix("if")
+            .filter(|rest| rest.starts_with(char::is_whitespace))
+            .map(|rest| rest.trim().to_string()),
+        _ => None,
+    }
+}
+
+// Variables a key cycles through several values, with the number of branches
+// that test each one, most used first
+pub fn find_swap_variables(docs: &[IniDocument]) -> Vec<SwapVariable> {
+    let mut cycled: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut branches: BTreeMap<String, usize> = BTreeMap::new();
+
+    for section in docs.iter().flat_map(|d| &d.sections) {
+        for entry in &section.entries {
+            if section.has_prefix("Key") && entry.key.starts_with('$') {
+                let values: Vec<String> = entry
+                    .value
+                    .split(',')
+                    .map(|v| v.trim().to_string())
+                    .filter(|v| !v.is_empty())
+                    .collect();
+                if values.len() > 1 {
+                    cycled.insert(entry.key.to_lowercase(), values);
+                }
+            }
+            if let Some((name, _)) = branch_condition(&entry.key, &entry.value)
+                .as_deref()
+                .and_then(parse_comparison)
+            {
+                *branches.entry(name).or_default() += 1;
+            }
+        }
+    }
+
+    let mut found: Vec<SwapVariable> = cycled
+        .into_iter()
+        .filter_map(|(name, values)| {
+            let count = branches.get(&name).copied().unwrap_or(0);
+            (count > 0).then_some(SwapVariable {
+                name,
+                values,
+                branches: count,
+            })
+        })
+        .collect();
+    found.sort_by_key(|s| std::cmp::Reverse(s.branches));
+    found
+}
+
+// One open `if` while walking a section
+struct Frame {
+    // Whether this chain branches on the swap variable
+    on_swap: bool,
+    // A branch of the chain was already chosen
+    taken: bool,
+    // Lines in the current branch are kept
+    keep: bool,
+}
+
+// Edits that turn one ini file into variant `value` of `swap`
+fn variant_edits(doc: &IniDocument, swap: &str, value: f64) -> Result<Vec<IniEdit>, String> {
+    let mut edits = Vec::new();
+    let matches = |condition: &str| -> Option<bool> {
+        parse_comparison(condition)
+            .filter(|(name, _)| name == swap)
+            .map(|(_, v)| v == value)
+    };
+
+    for entry in &doc.preamble {
+        if entry.key.eq_ignore_ascii_case("namespace") && !entry.value.is_empty() {
+            // Two variants active at once must not share a namespace
+            edits.push(IniEdit::SetValue {
+                line: entry.line,
+                value: format!("{}_{}", entry.value, value),
+            });
+        }
+    }
+
+    for section in &doc.sections {
+        // The key that cycled the variable goes; other variables it sets stay
+        let cycles: Vec<usize> = section
+            .entries
+            .iter()
+            .filter(|e| section.has_prefix("Key") && e.key.eq_ignore_ascii_case(swap))
+            .map(|e| e.line)
+            .collect();
+        if !cycles.is_empty() {
+            let sets_others = section
+                .entries
+                .iter()
+                .any(|e| e.key.starts_with('$') && !e.key.eq_ignore_ascii_case(swap));
+            if !sets_others {
+                edits.push(IniEdit::RemoveLine { line: section.line });
+                edits.extend(
+                    section
+                        .entries
+                        .iter()
+                        .map(|e| IniEdit::RemoveLine { line: e.line }),
+                );
+                continue;
+            }
+            edits.extend(cycles.into_iter().map(|line| IniEdit::RemoveLine { line }));
+        }
+
+        let mut stack: Vec<Frame> = Vec::new();
+        for entry in &section.entries {
+            let key = entry.key.to_lowercase();
+            let kept = stack.iter().all(|f| f.keep);
+            let remove = IniEdit::RemoveLine { line: entry.line };
+            let unbalanced = || format!("[{}] has unbalanced if/endif", section.name);
+
+            if key == "if" {
+                let chosen = matches(&entry.value);
+                if !kept || chosen.is_some() {
+                    edits.push(remove);
+                }
+                stack.push(Frame {
+                    on_swap: chosen.is_some(),
+                    taken: chosen == Some(true),
+                    keep: chosen.unwrap_or(true),
+                });
+                continue;
+            }
+
+            if key == "endif" {
+                let frame = stack.pop().ok_or_else(unbalanced)?;
+                if frame.on_swap || !kept {
+                    edits.push(remove);
+                }
+                continue;
+            }
+
+            if key == "elif" || key == "else" {
+                let frame = stack.last_mut().ok_or_else(unbalanced)?;
+                if !frame.on_swap {
+                    if !kept {
+                        edits.push(remove);
+                    }
+                    continue;
+                }
+                edits.push(remove);
+                let chosen = match branch_condition(&entry.key, &entry.value) {
+                    Some(condition) => matches(&condition).ok_or_else(|| {
+                        format!(
+                            "Line {} mixes {} with other conditions in one if/elif chain",
+                            entry.line, swap
+                        )
+                    })?,
+                    None => true,
+                };
+                frame.keep = chosen && !frame.taken;
+                frame.taken |= frame.keep;
+                continue;
+            }
+
+            if !kept {
+                edits.push(remove);
+                continue;
+            }
+
+            // The variable keeps meaning this variant wherever it is still read,
+            // and must not pick up a value 3DMigoto saved for the merged mod
+            let words: Vec<&str> = key.split_whitespace().collect();
+            if section.name.eq_ignore_ascii_case("Constants")
+                && words.len() > 1
+                && words.last() == Some(&swap)
+            {
+                edits.push(remove);
+                edits.push(IniEdit::InsertAfter {
+                    line: entry.line,
+                    text: format!("global {} = {}", swap, value),
+                });
+            }
+        }
+    }
+    Ok(edits)
+}
+
+// Hard link every file but the ini files, copying where links aren't possible
+fn link_tree(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_tree(&entry.path(), &target)?;
+        } else if !entry
+            .file_name()
+            .to_string_lossy()
+            .to_lowercase()
+            .ends_with(".ini")
+            && fs::hard_link(entry.path(), &target).is_err()
+        {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+fn load_docs(m: &Mod) -> Result<Vec<(String, Vec<u8>, IniDocument)>, String> {
+    let (root, files) = mod_ini_files(m)?;
+    files
+        .iter()
+        .map(|file| {
+            let ini_file = relative_path(&root, file);
+            let bytes =
+                fs::read(file).map_err(|e| format!("Failed to read {}: {}", ini_file, e))?;
+            let text = read_ini(file).map_err(|e| format!("Failed to read {}: {}", ini_file, e))?;
+            Ok((ini_file, bytes, parse_ini(&text)))
+        })
+        .collect()
+}
+
+async fn find_mod(mod_id: &str) -> Result<Mod, String> {
+    load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or_else(|| "Mod not found".to_string())
+}
+
+pub async fn detect_swap_variables(mod_id: &str) -> Result<Vec<SwapVariable>, String> {
+    let m = find_mod(mod_id).await?;
+    let docs: Vec<IniDocument> = load_docs(&m)?.into_iter().map(|(_, _, d)| d).collect();
+    Ok(find_swap_variables(&docs))
+}
+
+// Take back the variants of a split that failed part way
+async fn discard_variants(created: &[Mod]) {
+    for variant in created.iter().rev() {
+        if let Err(e) = remove_mod_metadata(&variant.id).await {
+            println!("Failed to remove {}: {}", variant.title, e);
+        }
+        let _ = fs::remove_dir_all(&variant.file_path);
+    }
+}
+
+// Split a merged mod into one new library mod per value of its swap variable,
+// the most used one unless `variable` names another
+pub async fn unmerge_mod(mod_id: &str, variable: Option<String>) -> Result<Vec<AddedMod>, String> {
+    let m = find_mod(mod_id).await?;
+    if !Path::new(&m.file_path).is_dir() {
+        return Err("Only folder mods can be split".to_string());
+    }
+    let files = load_docs(&m)?;
+    let docs: Vec<IniDocument> = files.iter().map(|(_, _, d)| d.clone()).collect();
+    let candidates = find_swap_variables(&docs);
+    let swap = match variable {
+        Some(name) => {
+            let name = format!("${}", name.trim().trim_start_matches('$')).to_lowercase();
+            candidates
+                .into_iter()
+                .find(|c| c.name == name)
+                .ok_or_else(|| format!("{} does not switch between variants", name))?
+        }
+        None => candidates
+            .into_iter()
+            .next()
+            .ok_or("No variant switch found in this mod")?,
+    };
+
+    // Work everything out before anything is written
+    let mut variants = Vec::new();
+    for value in &swap.values {
+        let number: f64 = value
+            .parse()
+            .map_err(|_| format!("{} cycles through a non-number: {}", swap.name, value))?;
+        let mut outputs = Vec::new();
+        for (ini_file, bytes, doc) in &files {
+            let edits = variant_edits(doc, &swap.name, number)
+                .map_err(|e| format!("{}: {}", ini_file, e))?;
+            outputs.push((ini_file.clone(), apply_edits(bytes, &edits)));
+        }
+        variants.push((value.clone(), outputs));
+    }
+
+    let storage_folder = storage_folder_for(m.character.as_deref())?;
+    let base_name = match sanitize_link_part(&m.title) {
+        t if t.is_empty() => "Variant".to_string(),
+        t => t,
+    };
+
+    let mut created = Vec::new();
+    for (value, outputs) in variants {
+        let destination = unique_destination(
+            &storage_folder,
+            &format!(
+                "{}_{}_{}",
+                base_name,
+                swap.name.trim_start_matches('$'),
+                value
+            ),
+        );
+        let result = link_tree(Path::new(&m.file_path), Path::new(&destination))
+            .map_err(|e| format!("Failed to create variant folder: {}", e))
+            .and_then(|_| {
+                outputs.iter().try_for_each(|(ini_file, bytes)| {
+                    fs::write(Path::new(&destination).join(ini_file), bytes)
+                        .map_err(|e| format!("Failed to write {}: {}", ini_file, e))
+                })
+            });
+        if let Err(e) = result {
+            let _ = fs::remove_dir_all(&destination);
+            discard_variants(&created).await;
+            return Err(e);
+        }
+
+        let mut variant = Mod {
+            id: Uuid::new_v4().to_string(),
+            title: format!("{} ({} = {})", m.title, swap.name, value),
+            description: Some(format!("Split from {}", m.title)),
+            thumbnail: m.thumbnail.clone(),
+            is_active: false,
+            date_added: Utc::now().to_rfc3339(),
+            character: m.character.clone(),
+            original_name: Path::new(&destination)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            file_path: destination,
+            is_missing: false,
+            link_name: String::new(),
+            variants: Vec::new(),
+            selected_variant: None,
+            group: None,
+            dependencies: Vec::new(),
+            load_order: None,
+        };
+        variant.link_name = build_link_name(&variant);
+        if let Err(e) = save_mod_metadata(&variant).await {
+            let _ = fs::remove_dir_all(&variant.file_path);
+            discard_variants(&created).await;
+            return Err(e);
+        }
+        created.push(variant);
+    }
+    Ok(created.into_iter().map(lint_added).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str, swap: &str, value: f64) -> String {
+        let edits = variant_edits(&parse_ini(text), swap, value).unwrap();
+        String::from_utf8(apply_edits(text.as_bytes(), &edits)).unwrap()
+    }
+
+    const MERGED: &str = "namespace = Ellen\\Merged\n[Constants]\nglobal persist $swapvar = 0\n\n[KeySwap]\nkey = VK_UP\n$swapvar = 0,1,2\n\n[TextureOverrideBody]\nhash = 1a2b3c4d\nif $swapvar == 0\n  ps-t0 = ResourceA\nelif $swapvar == 1\n  ps-t0 = ResourceB\nelse\n  ps-t0 = ResourceC\nendif\n";
+
+    #[test]
+    fn finds_the_cycled_variable_with_its_values() {
+        let found = find_swap_variables(&[parse_ini(MERGED)]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "$swapvar");
+        assert_eq!(found[0].values, ["0", "1", "2"]);
+        assert_eq!(found[0].branches, 2);
+    }
+
+    #[test]
+    fn keeps_one_branch_and_pins_the_variable() {
+        assert_eq!(
+            split(MERGED, "$swapvar", 1.0),
+            "namespace = Ellen\\Merged_1\n[Constants]\nglobal $swapvar = 1\n\n\n[TextureOverrideBody]\nhash = 1a2b3c4d\n  ps-t0 = ResourceB\n"
+        );
+        assert_eq!(
+            split(MERGED, "$swapvar", 2.0),
+            "namespace = Ellen\\Merged_2\n[Constants]\nglobal $swapvar = 2\n\n\n[TextureOverrideBody]\nhash = 1a2b3c4d\n  ps-t0 = ResourceC\n"
+        );
+    }
+
+    #[test]
+    fn nested_chains_on_and_off_the_swap_variable() {
+        let text = "[TextureOverrideBody]\nif $swapvar == 0\n  if $other == 1\n    ps-t1 = Glow\n  endif\n  if $swapvar == 0\n    ps-t0 = ResourceA\n  endif\nelif $swapvar == 1\n  if $other == 1\n    ps-t1 = Dark\n  elif $swapvar == 1\n    ps-t0 = ResourceB\n  endif\nendif\nif $other == 1\n  if $swapvar == 1\n    ps-t2 = Extra\n  endif\nendif\n";
+        assert_eq!(
+            split(text, "$swapvar", 0.0),
+            "[TextureOverrideBody]\n  if $other == 1\n    ps-t1 = Glow\n  endif\n    ps-t0 = ResourceA\nif $other == 1\nendif\n"
+        );
+        assert_eq!(
+            split(text, "$swapvar", 1.0),
+            "[TextureOverrideBody]\n  if $other == 1\n    ps-t1 = Dark\n  elif $swapvar == 1\n    ps-t0 = ResourceB\n  endif\nif $other == 1\n    ps-t2 = Extra\nendif\n"
+        );
+    }
+
+    #[test]
+    fn chains_mixing_the_swap_variable_with_others_are_errors() {
+        let doc = parse_ini("[TextureOverrideBody]\nif $swapvar == 0\n  ps-t0 = ResourceA\nelif $other == 1\n  ps-t0 = ResourceB\nendif\n");
+        let err = variant_edits(&doc, "$swapvar", 0.0).unwrap_err();
+        assert!(err.contains("mixes $swapvar"), "{}", err);
+    }
+
+    #[test]
+    fn keeps_a_key_section_that_sets_other_variables() {
+        let text = "[KeySwap]\nkey = VK_UP\n$swapvar = 0,1\n$glow = 1,0\n";
+        assert_eq!(
+            split(text, "$swapvar", 0.0),
+            "[KeySwap]\nkey = VK_UP\n$glow = 1,0\n"
+        );
+    }
+
+    #[test]
+    fn unbalanced_chains_are_errors() {
+        let doc = parse_ini("[TextureOverrideBody]\nendif\n");
+        assert!(variant_edits(&doc, "$swapvar", 0.0).is_err());
+    }
+}
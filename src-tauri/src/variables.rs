@@ -0,0 +1,336 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::activation::ActivationMode;
+use crate::ini::{apply_edits, parse_ini, IniEdit};
+use crate::links::link_folder_name;
+use crate::manifest::{manifest_for, ModManifest};
+use crate::storage::{load_all_mods, load_settings};
+use crate::{Mod, PresetVariable};
+
+// 3DMigoto keeps `persist` variables in d3dx_user.ini, next to the mods
+// folder, as `$\<scope>\<name> = <value>` lines under [Constants]. The scope is
+// the ini's namespace, or else its path from the 3DMigoto folder, which runs
+// through the link name; that is why the values are lost when a mod is
+// relinked under another name.
+const USER_INI: &str = "d3dx_user.ini";
+const CONSTANTS_SECTION: &str = "Constants";
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariableState {
+    pub ini_file: String,
+    // Including the leading `$`
+    pub name: String,
+    // The d3dx_user.ini key, lower-case
+    pub key: String,
+    pub default_value: Option<String>,
+    // None until 3DMigoto (or Aether) has saved a value
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariableValue {
+    pub ini_file: String,
+    pub name: String,
+    // None forgets the saved value, so the mod starts from its default
+    pub value: Option<String>,
+}
+
+pub fn user_ini_path(zzmi_path: &str) -> PathBuf {
+    let mods_dir = Path::new(zzmi_path);
+    mods_dir.parent().unwrap_or(mods_dir).join(USER_INI)
+}
+
+// The d3dx_user.ini scope of each ini file in the mod, as deployed
+fn ini_scopes(
+    zzmi_path: &str,
+    mode: ActivationMode,
+    m: &Mod,
+    manifest: &ModManifest,
+) -> HashMap<String, String> {
+    let mods_folder = Path::new(zzmi_path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // A linked single-file mod is the ini itself; copies get their own folder
+    let linked_file = Path::new(&m.file_path).is_file() && !mode.copies_files();
+    // Only the selected variant is deployed, so its folder is the mod folder
+    let variant_prefix = m
+        .selected_variant
+        .as_ref()
+        .map(|v| format!("{}/", v))
+        .unwrap_or_default();
+
+    manifest
+        .ini_files
+        .iter()
+        .map(|ini| {
+            let scope = match &ini.namespace {
+                Some(namespace) => namespace.clone(),
+                None if linked_file => format!("{}\\{}", mods_folder, link_folder_name(m)),
+                None => format!(
+                    "{}\\{}\\{}",
+                    mods_folder,
+                    link_folder_name(m),
+                    ini.path
+                        .strip_prefix(&variant_prefix)
+                        .unwrap_or(&ini.path)
+                        .replace('/', "\\")
+                ),
+            };
+            (ini.path.clone(), scope)
+        })
+        .collect()
+}
+
+fn variable_key(scope: &str, name: &str) -> String {
+    format!("$\\{}\\{}", scope, name.trim_start_matches('$')).to_lowercase()
+}
+
+// Saved values by lower-case key, with the line each one is on
+fn read_saved_values(bytes: &[u8]) -> HashMap<String, (String, usize)> {
+    let text = String::from_utf8_lossy(bytes);
+    parse_ini(text.trim_start_matches('\u{feff}'))
+        .sections
+        .into_iter()
+        .filter(|s| s.name.eq_ignore_ascii_case(CONSTANTS_SECTION))
+        .flat_map(|s| s.entries)
+        .filter(|e| e.key.starts_with("$\\"))
+        .map(|e| (e.key.to_lowercase(), (e.value, e.line)))
+        .collect()
+}
+
+fn read_user_ini(path: &Path) -> Result<Vec<u8>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+// Every persisted variable of the mod with its saved value
+pub fn variable_states(
+    zzmi_path: &str,
+    mode: ActivationMode,
+    m: &Mod,
+    manifest: &ModManifest,
+) -> Result<Vec<VariableState>, String> {
+    let saved = read_saved_values(&read_user_ini(&user_ini_path(zzmi_path))?);
+    let scopes = ini_scopes(zzmi_path, mode, m, manifest);
+
+    Ok(manifest
+        .variables
+        .iter()
+        .filter(|v| v.persist)
+        .filter_map(|v| {
+            let key = variable_key(scopes.get(&v.ini_file)?, &v.name);
+            Some(VariableState {
+                ini_file: v.ini_file.clone(),
+                name: v.name.clone(),
+                value: saved.get(&key).map(|(value, _)| value.clone()),
+                default_value: v.default_value.clone(),
+                key,
+            })
+        })
+        .collect())
+}
+
+// Last line of the [Constants] section
+fn constants_end(bytes: &[u8]) -> Option<usize> {
+    let text = String::from_utf8_lossy(bytes);
+    let constants = parse_ini(&text)
+        .sections
+        .into_iter()
+        .find(|s| s.name.eq_ignore_ascii_case(CONSTANTS_SECTION))?;
+    Some(
+        constants
+            .entries
+            .last()
+            .map(|e| e.line)
+            .unwrap_or(constants.line),
+    )
+}
+
+// Set or clear d3dx_user.ini keys, leaving every other line as it is
+pub fn write_saved_values(
+    zzmi_path: &str,
+    updates: &[(String, Option<String>)],
+) -> Result<(), String> {
+    let path = user_ini_path(zzmi_path);
+    let mut bytes = read_user_ini(&path)?;
+
+    let end = match constants_end(&bytes) {
+        Some(end) => end,
+        None => {
+            if !bytes.is_empty() && !bytes.ends_with(b"\n") {
+                let crlf = bytes.windows(2).any(|w| w == b"\r\n");
+                bytes.extend_from_slice(if crlf { b"\r\n" } else { b"\n" });
+            }
+            bytes.extend_from_slice(format!("[{}]", CONSTANTS_SECTION).as_bytes());
+            constants_end(&bytes).ok_or("Failed to add [Constants] to d3dx_user.ini")?
+        }
+    };
+    let saved = read_saved_values(&bytes);
+
+    let mut edits = Vec::new();
+    for (key, value) in updates {
+        match (saved.get(key), value) {
+            (Some((_, line)), Some(value)) => edits.push(IniEdit::SetValue {
+                line: *line,
+                value: value.clone(),
+            }),
+            (Some((_, line)), None) => edits.push(IniEdit::RemoveLine { line: *line }),
+            (None, Some(value)) => edits.push(IniEdit::InsertAfter {
+                line: end,
+                text: format!("{} = {}", key, value),
+            }),
+            (None, None) => {}
+        }
+    }
+
+    // Written next to it and renamed over it, so a crash or a full disk
+    // can't leave every mod's saved states truncated
+    let temp = path.with_extension("ini.tmp");
+    let written = fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(&apply_edits(&bytes, &edits))?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, &path));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(format!("Failed to write {}: {}", path.display(), e));
+    }
+    Ok(())
+}
+
+async fn zzmi_settings() -> Result<(String, ActivationMode), String> {
+    let settings = load_settings().await?;
+    let zzmi_path = settings
+        .zzmi_mods_path
+        .ok_or("ZZMI mods path not configured. Please set it in settings.")?;
+    Ok((zzmi_path, settings.activation_mode))
+}
+
+async fn zzmi_context(mod_id: &str) -> Result<(String, ActivationMode, Mod), String> {
+    let (zzmi_path, mode) = zzmi_settings().await?;
+    let m = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or("Mod not found")?;
+    Ok((zzmi_path, mode, m))
+}
+
+pub async fn get_mod_variables(mod_id: &str) -> Result<Vec<VariableState>, String> {
+    let (zzmi_path, mode, m) = zzmi_context(mod_id).await?;
+    let manifest = manifest_for(&m).await?;
+    variable_states(&zzmi_path, mode, &m, &manifest)
+}
+
+// Resolve `values` to d3dx_user.ini keys of the mod's persisted variables
+pub fn saved_value_updates(
+    states: &[VariableState],
+    values: &[VariableValue],
+) -> Result<Vec<(String, Option<String>)>, String> {
+    values
+        .iter()
+        .map(|v| {
+            let state = states
+                .iter()
+                .find(|s| s.ini_file == v.ini_file && s.name.eq_ignore_ascii_case(&v.name))
+                .ok_or_else(|| {
+                    format!("{} in {} is not a persisted variable", v.name, v.ini_file)
+                })?;
+            let value = match &v.value {
+                Some(value) => {
+                    let value = value.trim();
+                    value
+                        .parse::<f64>()
+                        .map_err(|_| format!("{} must be a number, not '{}'", v.name, value))?;
+                    Some(value.to_string())
+                }
+                None => None,
+            };
+            Ok((state.key.clone(), value))
+        })
+        .collect()
+}
+
+// Takes effect the next time the game starts; 3DMigoto rewrites the file
+// from memory when it exits
+pub async fn set_mod_variables(
+    mod_id: &str,
+    values: Vec<VariableValue>,
+) -> Result<Vec<VariableState>, String> {
+    let (zzmi_path, mode, m) = zzmi_context(mod_id).await?;
+    let manifest = manifest_for(&m).await?;
+    let states = variable_states(&zzmi_path, mode, &m, &manifest)?;
+    write_saved_values(&zzmi_path, &saved_value_updates(&states, &values)?)?;
+    variable_states(&zzmi_path, mode, &m, &manifest)
+}
+
+// The current value of every persisted variable of `mods`, for a preset.
+// Mods that can't be read are left out.
+pub async fn capture_variables(mods: &[Mod]) -> Result<Vec<PresetVariable>, String> {
+    let (zzmi_path, mode) = zzmi_settings().await?;
+    let mut captured = Vec::new();
+    for m in mods {
+        let manifest = match manifest_for(m).await {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                println!("Skipping toggle states of {}: {}", m.title, e);
+                continue;
+            }
+        };
+        for state in variable_states(&zzmi_path, mode, m, &manifest)? {
+            captured.push(PresetVariable {
+                mod_id: m.id.clone(),
+                ini_file: state.ini_file,
+                name: state.name,
+                value: state.value,
+            });
+        }
+    }
+    Ok(captured)
+}
+
+// Write captured values back for the given mods in one go. Variables a mod no
+// longer declares are skipped. Returns how many values were written.
+pub async fn restore_variables(
+    zzmi_path: &str,
+    mode: ActivationMode,
+    mods: &[Mod],
+    variables: &[PresetVariable],
+) -> Result<usize, String> {
+    let with_values: HashSet<&str> = variables.iter().map(|v| v.mod_id.as_str()).collect();
+    let mut updates = Vec::new();
+    for m in mods.iter().filter(|m| with_values.contains(m.id.as_str())) {
+        let manifest = manifest_for(m).await?;
+        let states = variable_states(zzmi_path, mode, m, &manifest)?;
+        let values: Vec<VariableValue> = variables
+            .iter()
+            .filter(|v| v.mod_id == m.id)
+            .filter(|v| {
+                states
+                    .iter()
+                    .any(|s| s.ini_file == v.ini_file && s.name.eq_ignore_ascii_case(&v.name))
+            })
+            .map(|v| VariableValue {
+                ini_file: v.ini_file.clone(),
+                name: v.name.clone(),
+                value: v.value.clone(),
+            })
+            .collect();
+        updates.extend(saved_value_updates(&states, &values)?);
+    }
+
+    if !updates.is_empty() {
+        write_saved_values(zzmi_path, &updates)?;
+    }
+    Ok(updates.len())
+}
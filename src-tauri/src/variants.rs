@@ -0,0 +1,105 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::activation::{activator_for, refresh_deployment};
+use crate::manifest::{ini_files_at, is_disabled_name};
+use crate::storage::{load_all_mods, load_settings, save_mod_metadata};
+use crate::Mod;
+
+// Packs often ship several versions of a mod side by side (`Variant A/`,
+// `Variant B/`, `NoHair/`). The whole pack stays in the library, but only the
+// selected sub-folder is linked or copied into ZZMI, so 3DMigoto never loads
+// two of them at once.
+
+fn is_ini_name(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    lower.ends_with(".ini") && lower != "desktop.ini"
+}
+
+// Sub-folders of `root` that are complete mods of their own, sorted. A folder
+// with an ini file of its own is one mod whose sub-folders belong to it, and
+// a single sub-folder is no choice at all; both give an empty list.
+pub fn find_variants(root: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+
+    let mut variants = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if path.is_dir() {
+            let has_ini = ini_files_at(&path)
+                .map(|(_, files)| !files.is_empty())
+                .unwrap_or(false);
+            if has_ini && !is_disabled_name(&name) {
+                variants.push(name);
+            }
+        } else if is_ini_name(&name) {
+            return Vec::new();
+        }
+    }
+
+    if variants.len() < 2 {
+        return Vec::new();
+    }
+    variants.sort_by_key(|v| v.to_lowercase());
+    variants
+}
+
+// The folder that goes into ZZMI: the selected variant, or the whole mod
+pub fn deployed_path(m: &Mod) -> PathBuf {
+    let root = Path::new(&m.file_path);
+    match &m.selected_variant {
+        Some(variant) => root.join(variant),
+        None => root.to_path_buf(),
+    }
+}
+
+// Put the mod into ZZMI again after its variant changed. Links are replaced
+// in place; copies are redeployed or, while inactive, dropped.
+async fn redeploy(m: &Mod) -> Result<(), String> {
+    let settings = load_settings().await?;
+    match settings.zzmi_mods_path {
+        Some(zzmi_path) if m.is_active && !settings.activation_mode.copies_files() => {
+            activator_for(settings.activation_mode)
+                .activate(&zzmi_path, m)
+                .map_err(|e| format!("Failed to relink {}: {}", m.title, e))
+        }
+        _ => refresh_deployment(m).await,
+    }
+}
+
+// Deploy `variant` of the mod from now on; None deploys the whole folder.
+// The variant list is detected again, so packs installed before variants
+// were known can be switched too.
+pub async fn select_mod_variant(mod_id: &str, variant: Option<String>) -> Result<Mod, String> {
+    let previous = load_all_mods()
+        .await?
+        .into_iter()
+        .find(|m| m.id == mod_id)
+        .ok_or("Mod not found")?;
+
+    let mut m = previous.clone();
+    m.variants = find_variants(Path::new(&m.file_path));
+    m.selected_variant = match variant {
+        Some(name) => Some(
+            m.variants
+                .iter()
+                .find(|v| v.eq_ignore_ascii_case(name.trim()))
+                .cloned()
+                .ok_or_else(|| format!("{} is not a variant of {}", name, m.title))?,
+        ),
+        None => None,
+    };
+
+    save_mod_metadata(&m).await?;
+    if let Err(e) = redeploy(&m).await {
+        save_mod_metadata(&previous).await?;
+        if let Err(restore) = redeploy(&previous).await {
+            println!("Failed to restore {}: {}", previous.title, restore);
+        }
+        return Err(e);
+    }
+    Ok(m)
+}
@@ -12,6 +12,35 @@ import { useStats } from "./useStats";
 import { Mod as UnifiedMod } from "../types/mod";
 import { toast } from "react-hot-toast";
 
+// Hash override shared by several active mods
+interface HashConflict {
+  kind: "texture" | "shader";
+  hash: string;
+  matchFirstIndex: string | null;
+  mods: { modId: string; title: string; iniFile: string; section: string }[];
+}
+
+interface ToggleResult {
+  isActive: boolean;
+  conflicts: HashConflict[];
+  // Mods of the same exclusive slot that were deactivated to make room
+  deactivated: string[];
+  // Dependencies that were activated along with the mod
+  activated: string[];
+}
+
+// What the backend's lint found in a mod's files
+interface LintReport {
+  modId: string;
+  diagnostics: { severity: "error" | "warning"; code: string; message: string }[];
+}
+
+// A mod that was just added to the library, with its lint report
+interface AddedMod {
+  mod: RustMod;
+  lint: LintReport;
+}
+
 // Rust backend response type (snake_case)
 interface RustMod {
   id: string;
@@ -108,7 +137,7 @@ const useModsInternal = (): UseModsReturn => {
     setLoading(true);
     setError(null);
     try {
-      const result = await invoke<RustMod>("install_mod", {
+      const { mod: result, lint } = await invoke<AddedMod>("install_mod", {
         filePath,
         title,
         character,
@@ -130,6 +159,21 @@ const useModsInternal = (): UseModsReturn => {
       };
 
       setMods((prev) => [...prev, convertedMod]);
+
+      // Surface problems that would only show up in game
+      const errors = lint.diagnostics.filter((d) => d.severity === "error").length;
+      const warnings = lint.diagnostics.length - errors;
+      if (errors > 0) {
+        toast.error(`${result.title}: ${errors} problem(s) found in the mod files`);
+      }
+      if (warnings > 0) {
+        toast(`${result.title}: ${warnings} possible mistake(s) in the mod files`, {
+          icon: "⚠️",
+        });
+      }
+      lint.diagnostics.forEach((d) =>
+        console.warn(`${result.title} [${d.severity}] ${d.code}: ${d.message}`)
+      );
       return convertedMod;
     } catch (err) {
       console.error("installMod error details:", err);
@@ -176,14 +220,33 @@ const useModsInternal = (): UseModsReturn => {
       );
 
       // Call backend (returns new is_active state, but false can be valid when deactivating)
-      const result = await invoke<boolean>("toggle_mod_active", { modId });
-      console.log("Toggle result (new is_active):", result);
+      const result = await invoke<ToggleResult>("toggle_mod_active", { modId });
+      console.log("Toggle result (new is_active):", result.isActive);
       // Reconcile local state with backend result immediately
       setMods((prev) =>
-        prev.map((mod) => (mod.id === modId ? { ...mod, isActive: result } : mod))
+        prev.map((mod) => {
+          if (mod.id === modId) return { ...mod, isActive: result.isActive };
+          if (result.deactivated.includes(mod.id)) return { ...mod, isActive: false };
+          if (result.activated.includes(mod.id)) return { ...mod, isActive: true };
+          return mod;
+        })
       );
       // Toast feedback
-      toast.success(result ? "Mod activated" : "Mod deactivated");
+      toast.success(result.isActive ? "Mod activated" : "Mod deactivated");
+      if (result.activated.length > 0) {
+        toast.success(`Also activated ${result.activated.length} required mod(s)`);
+      }
+      if (result.deactivated.length > 0) {
+        toast(`Deactivated ${result.deactivated.length} mod(s) sharing its slot`);
+      }
+      if (result.conflicts.length > 0) {
+        const others = new Set(
+          result.conflicts.flatMap((c) =>
+            c.mods.filter((m) => m.modId !== modId).map((m) => m.title)
+          )
+        );
+        toast.error(`Overrides the same hashes as: ${[...others].join(", ")}`);
+      }
       // Reconcile with backend in background to keep all pages in sync
       fetchMods(true);
       fetchStats(true); // Keep this to ensure long-term consistency
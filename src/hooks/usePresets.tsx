@@ -62,10 +62,13 @@ const usePresetsInternal = (): UsePresetsReturn => {
   const applyPreset = async (presetId: string): Promise<boolean> => {
     setError(null);
     try {
-      await invoke("apply_preset", { presetId });
+      const plan = await invoke<{ hashConflicts: unknown[] }>("apply_preset", { presetId });
       await Promise.all([fetchMods(true), fetchStats(true)]);
       const presetName = presets.find((p) => p.id === presetId)?.name ?? "Preset";
       toast.success(`${presetName} applied`);
+      if (plan.hashConflicts.length > 0) {
+        toast.error(`${presetName} has mods that override the same hashes`);
+      }
       return true;
     } catch (err) {
       const msg = String(err);